@@ -0,0 +1,67 @@
+//! Throughput of the catr binary on a large file, per output path.
+//!
+//! Run with `cargo bench -p catr`; output goes to /dev/null so that only
+//! catr itself is measured.
+
+use std::{
+    fs,
+    io::Write,
+    path::Path,
+    process::{Command, Stdio},
+};
+
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+
+/// Size of the generated input.
+const SIZE: usize = 64 * 1024 * 1024;
+
+/// Each benchmark: a name and the catr options it runs with.
+const CASES: &[(&str, &[&str])] = &[
+    // Handed to the kernel
+    ("verbatim", &[]),
+    // Still a plain copy, but through a read buffer after sniffing the input
+    ("buffered", &["--decompress=auto"]),
+    ("number", &["-n"]),
+    ("number-nonblank", &["-b"]),
+    ("show-all", &["-A"]),
+];
+
+fn write_input(path: &Path) {
+    let mut file = fs::File::create(path).unwrap();
+    let line = b"The quick brown fox jumps over the lazy dog\t0123456789\n";
+    let blank = b"\n";
+    let mut written = 0;
+    while written < SIZE {
+        file.write_all(line).unwrap();
+        file.write_all(blank).unwrap();
+        written += line.len() + blank.len();
+    }
+}
+
+fn throughput(c: &mut Criterion) {
+    let dir = tempfile::tempdir().unwrap();
+    let input = dir.path().join("input.txt");
+    write_input(&input);
+    let len = fs::metadata(&input).unwrap().len();
+
+    let mut group = c.benchmark_group("catr");
+    group.throughput(Throughput::Bytes(len));
+    group.sample_size(10);
+    for (name, args) in CASES {
+        group.bench_with_input(BenchmarkId::from_parameter(name), args, |b, args| {
+            b.iter(|| {
+                let status = Command::new(env!("CARGO_BIN_EXE_catr"))
+                    .args(*args)
+                    .arg(&input)
+                    .stdout(Stdio::null())
+                    .status()
+                    .unwrap();
+                assert!(status.success());
+            })
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, throughput);
+criterion_main!(benches);
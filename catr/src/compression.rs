@@ -0,0 +1,105 @@
+use std::io::{self, BufRead, BufReader};
+
+use anyhow::Result;
+use bzip2::bufread::MultiBzDecoder;
+use clap::ValueEnum;
+use flate2::bufread::MultiGzDecoder;
+use xz2::bufread::XzDecoder;
+
+use crate::BUFFER_SIZE;
+
+/// When inputs are run through a decompressor.
+#[derive(Clone, Copy, Debug, PartialEq, ValueEnum)]
+pub enum Decompress {
+    /// Copy every input as is
+    Never,
+    /// Decompress inputs that start with a known magic number, copy the rest
+    Auto,
+    /// Decompress every input, failing on the ones that are not compressed
+    Always,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Format {
+    Gzip,
+    Bzip2,
+    Xz,
+    Zstd,
+}
+
+impl Format {
+    /// Sniffs the compression format from the first bytes of a stream.
+    fn detect(header: &[u8]) -> Option<Self> {
+        if header.starts_with(&[0x1f, 0x8b]) {
+            Some(Format::Gzip)
+        } else if header.starts_with(b"BZh") {
+            Some(Format::Bzip2)
+        } else if header.starts_with(&[0xfd, b'7', b'z', b'X', b'Z', 0x00]) {
+            Some(Format::Xz)
+        } else if header.starts_with(&[0x28, 0xb5, 0x2f, 0xfd]) {
+            Some(Format::Zstd)
+        } else {
+            None
+        }
+    }
+}
+
+/// Wraps `buff` in the decompressor matching its magic number, if any.
+///
+/// Concatenated streams (`cat a.gz b.gz`) are decoded as a whole, like zcat.
+pub fn decompress(mut buff: Box<dyn BufRead>, when: Decompress) -> Result<Box<dyn BufRead>> {
+    if when == Decompress::Never {
+        return Ok(buff);
+    }
+    let format = Format::detect(buff.fill_buf()?);
+    Ok(match format {
+        Some(Format::Gzip) => Box::new(BufReader::with_capacity(
+            BUFFER_SIZE,
+            MultiGzDecoder::new(buff),
+        )),
+        Some(Format::Bzip2) => Box::new(BufReader::with_capacity(
+            BUFFER_SIZE,
+            MultiBzDecoder::new(buff),
+        )),
+        Some(Format::Xz) => Box::new(BufReader::with_capacity(
+            BUFFER_SIZE,
+            XzDecoder::new_multi_decoder(buff),
+        )),
+        Some(Format::Zstd) => Box::new(BufReader::with_capacity(
+            BUFFER_SIZE,
+            zstd::Decoder::with_buffer(buff)?,
+        )),
+        None if when == Decompress::Always => {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "not in a known compressed format",
+            )
+            .into())
+        }
+        None => buff,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    #[test]
+    fn test_detect() {
+        assert_eq!(Format::detect(&[0x1f, 0x8b, 0x08]), Some(Format::Gzip));
+        assert_eq!(Format::detect(b"BZh91AY&SY"), Some(Format::Bzip2));
+        assert_eq!(
+            Format::detect(&[0xfd, b'7', b'z', b'X', b'Z', 0x00, 0x00]),
+            Some(Format::Xz)
+        );
+        assert_eq!(
+            Format::detect(&[0x28, 0xb5, 0x2f, 0xfd, 0x24]),
+            Some(Format::Zstd)
+        );
+        assert_eq!(Format::detect(b"BZ"), None);
+        assert_eq!(Format::detect(b"plain text"), None);
+        assert_eq!(Format::detect(b""), None);
+    }
+}
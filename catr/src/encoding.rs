@@ -0,0 +1,168 @@
+use std::io::{self, BufRead, BufReader, Read};
+
+use encoding_rs::{Encoding, UTF_8};
+use encoding_rs_io::DecodeReaderBytesBuilder;
+
+use crate::BUFFER_SIZE;
+
+/// Conversion of inputs from their encoding to the output one.
+#[derive(Clone, Copy, Debug)]
+pub struct Transcoding {
+    /// The encoding of the inputs; `None` sniffs the BOM, falling back to UTF-8.
+    pub from: Option<&'static Encoding>,
+    pub to: &'static Encoding,
+}
+
+/// Parses an encoding label such as `latin1`, `utf-16le` or `shift_jis`.
+///
+/// `auto` stands for BOM sniffing and maps to `None`.
+pub fn parse_from_encoding(label: &str) -> Result<Option<&'static Encoding>, String> {
+    match label {
+        "auto" => Ok(None),
+        _ => parse_encoding(label).map(Some),
+    }
+}
+
+/// Parses an encoding that output can be written in.
+///
+/// UTF-16 is left out: line numbers and the `^X` notation are ASCII, and
+/// would not make sense spliced into UTF-16 text.
+pub fn parse_to_encoding(label: &str) -> Result<&'static Encoding, String> {
+    let encoding = parse_encoding(label)?;
+    if encoding.output_encoding() != encoding {
+        return Err(format!("cannot write output in {}", encoding.name()));
+    }
+    Ok(encoding)
+}
+
+fn parse_encoding(label: &str) -> Result<&'static Encoding, String> {
+    Encoding::for_label(label.as_bytes()).ok_or_else(|| format!("unknown encoding: \"{label}\""))
+}
+
+/// Decodes `buff` to UTF-8, then encodes it into the output encoding.
+///
+/// A BOM always wins over the declared input encoding, and is removed.
+pub fn transcode(buff: Box<dyn BufRead>, transcoding: Transcoding) -> Box<dyn BufRead> {
+    let decoded = DecodeReaderBytesBuilder::new()
+        .encoding(transcoding.from)
+        .bom_override(true)
+        .strip_bom(true)
+        .build(buff);
+    if transcoding.to == UTF_8 {
+        return Box::new(BufReader::with_capacity(BUFFER_SIZE, decoded));
+    }
+    Box::new(BufReader::with_capacity(
+        BUFFER_SIZE,
+        Encode {
+            inner: decoded,
+            encoding: transcoding.to,
+            pending: Vec::new(),
+            encoded: Vec::new(),
+            pos: 0,
+        },
+    ))
+}
+
+/// Encodes the UTF-8 coming out of `inner`; characters the encoding lacks
+/// become HTML numeric references, as encoding_rs does.
+struct Encode<R> {
+    inner: R,
+    encoding: &'static Encoding,
+    /// UTF-8 read but not encoded yet, because it ends mid-character.
+    pending: Vec<u8>,
+    encoded: Vec<u8>,
+    pos: usize,
+}
+
+impl<R: Read> Read for Encode<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        while self.pos == self.encoded.len() {
+            if !self.refill()? {
+                return Ok(0);
+            }
+        }
+        let len = buf.len().min(self.encoded.len() - self.pos);
+        buf[..len].copy_from_slice(&self.encoded[self.pos..self.pos + len]);
+        self.pos += len;
+        Ok(len)
+    }
+}
+
+impl<R: Read> Encode<R> {
+    /// Encodes the next chunk of input, returning false at the end of it.
+    fn refill(&mut self) -> io::Result<bool> {
+        let mut chunk = [0; 8192];
+        let len = self.inner.read(&mut chunk)?;
+        if len == 0 && self.pending.is_empty() {
+            return Ok(false);
+        }
+        self.pending.extend_from_slice(&chunk[..len]);
+        let valid = match std::str::from_utf8(&self.pending) {
+            Ok(text) => text.len(),
+            Err(e) if e.error_len().is_none() && len > 0 => e.valid_up_to(),
+            Err(_) => self.pending.len(),
+        };
+        let text = String::from_utf8_lossy(&self.pending[..valid]);
+        self.encoded = self.encoding.encode(&text).0.into_owned();
+        self.pos = 0;
+        self.pending.drain(..valid);
+        Ok(true)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    fn transcode_all(input: &'static [u8], from: Option<&str>, to: &str) -> Vec<u8> {
+        let transcoding = Transcoding {
+            from: from.map(|label| parse_encoding(label).unwrap()),
+            to: parse_to_encoding(to).unwrap(),
+        };
+        let mut output = Vec::new();
+        transcode(Box::new(input), transcoding)
+            .read_to_end(&mut output)
+            .unwrap();
+        output
+    }
+
+    #[test]
+    fn test_transcode() {
+        assert_eq!(
+            transcode_all(b"\xff\xfeZ\x00o\x00\xe9\x00\n\x00", None, "utf-8"),
+            "Zoé\n".as_bytes()
+        );
+        assert_eq!(
+            transcode_all(b"\xef\xbb\xbfZo\xc3\xa9\n", None, "latin1"),
+            b"Zo\xe9\n"
+        );
+        assert_eq!(
+            transcode_all(b"Zo\xe9\n", Some("latin1"), "utf-8"),
+            "Zoé\n".as_bytes()
+        );
+        assert_eq!(
+            transcode_all("€ ✓\n".as_bytes(), None, "latin1"),
+            b"\x80 &#10003;\n"
+        );
+    }
+
+    #[test]
+    fn test_parse_encodings() {
+        assert_eq!(parse_from_encoding("auto"), Ok(None));
+        assert_eq!(
+            parse_from_encoding("UTF-16LE"),
+            Ok(Some(encoding_rs::UTF_16LE))
+        );
+        assert_eq!(parse_to_encoding("latin1"), Ok(encoding_rs::WINDOWS_1252));
+        assert_eq!(
+            parse_to_encoding("utf-16be").unwrap_err(),
+            "cannot write output in UTF-16BE"
+        );
+        assert_eq!(
+            parse_from_encoding("klingon").unwrap_err(),
+            r#"unknown encoding: "klingon""#
+        );
+    }
+}
@@ -0,0 +1,73 @@
+use std::{
+    fs::{self, File, Metadata},
+    io::{self, BufReader, Seek, SeekFrom},
+};
+
+use crate::BUFFER_SIZE;
+
+/// A file that keeps being read as data is appended to it, like `tail -F`.
+///
+/// Truncation rewinds to the start of the file; when the path is replaced
+/// (log rotation), the old file is drained before switching to the new one.
+#[derive(Debug)]
+pub struct Follower {
+    path: String,
+    file: File,
+}
+
+impl Follower {
+    pub fn open(path: &str) -> io::Result<Self> {
+        Ok(Follower {
+            path: path.to_string(),
+            file: File::open(path)?,
+        })
+    }
+
+    /// A reader over everything from the current position to the end of the file.
+    pub fn reader(&self) -> BufReader<&File> {
+        BufReader::with_capacity(BUFFER_SIZE, &self.file)
+    }
+
+    /// Returns a reader over the data that showed up since the last read, if any.
+    pub fn poll(&mut self) -> io::Result<Option<BufReader<&File>>> {
+        let pos = (&self.file).stream_position()?;
+        let len = self.file.metadata()?.len();
+        if len < pos {
+            eprintln!("{}: file truncated", self.path);
+            (&self.file).seek(SeekFrom::Start(0))?;
+        } else if len == pos {
+            match self.replacement() {
+                Some(file) => {
+                    eprintln!("{} has been replaced; following new file", self.path);
+                    self.file = file;
+                }
+                None => return Ok(None),
+            }
+        }
+        Ok(Some(self.reader()))
+    }
+
+    /// Opens the file now living at our path, if it is not the one being read.
+    ///
+    /// A path that is missing, mid-rotation, is simply waited for.
+    fn replacement(&self) -> Option<File> {
+        let current = fs::metadata(&self.path).ok()?;
+        if is_same_file(&current, &self.file.metadata().ok()?) {
+            return None;
+        }
+        File::open(&self.path).ok()
+    }
+}
+
+/// Whether two metadata describe the same file on disk.
+#[cfg(unix)]
+pub fn is_same_file(a: &Metadata, b: &Metadata) -> bool {
+    use std::os::unix::fs::MetadataExt;
+
+    a.dev() == b.dev() && a.ino() == b.ino()
+}
+
+#[cfg(not(unix))]
+pub fn is_same_file(_a: &Metadata, _b: &Metadata) -> bool {
+    true
+}
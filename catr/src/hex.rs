@@ -0,0 +1,90 @@
+use std::io::{self, Read, Write};
+
+const BYTES_PER_LINE: usize = 16;
+
+/// How far into an input to look for signs of binary data.
+const SNIFF_LEN: usize = 8000;
+
+/// Writes `input` in the layout of `xxd`: offset, hex pairs grouped by two
+/// bytes, then the printable ASCII characters.
+pub fn dump(mut input: impl Read, out: &mut impl Write) -> io::Result<()> {
+    let mut chunk = [0; BYTES_PER_LINE];
+    let mut offset = 0;
+    loop {
+        let len = read_chunk(&mut input, &mut chunk)?;
+        if len == 0 {
+            return Ok(());
+        }
+        out.write_all(format_line(offset, &chunk[..len]).as_bytes())?;
+        offset += len;
+    }
+}
+
+/// Fills `chunk` as much as the input allows, so that short reads from pipes
+/// do not produce short lines.
+fn read_chunk(input: &mut impl Read, chunk: &mut [u8]) -> io::Result<usize> {
+    let mut len = 0;
+    while len < chunk.len() {
+        match input.read(&mut chunk[len..]) {
+            Ok(0) => break,
+            Ok(n) => len += n,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(len)
+}
+
+fn format_line(offset: usize, bytes: &[u8]) -> String {
+    let mut hex = String::with_capacity(40);
+    for (i, byte) in bytes.iter().enumerate() {
+        hex.push_str(&format!("{byte:02x}"));
+        if i % 2 == 1 {
+            hex.push(' ');
+        }
+    }
+    let ascii: String = bytes
+        .iter()
+        .map(|&b| {
+            if b.is_ascii_graphic() || b == b' ' {
+                b as char
+            } else {
+                '.'
+            }
+        })
+        .collect();
+    format!("{offset:08x}: {hex:<40} {ascii}\n")
+}
+
+/// Whether the start of an input looks like binary data rather than text.
+///
+/// Like git and grep, a NUL byte is taken as the tell.
+pub fn is_binary(start: &[u8]) -> bool {
+    start.iter().take(SNIFF_LEN).any(|&b| b == 0)
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    #[test]
+    fn test_format_line() {
+        assert_eq!(
+            format_line(0, b"The quick brown "),
+            "00000000: 5468 6520 7175 6963 6b20 6272 6f77 6e20  The quick brown \n"
+        );
+        assert_eq!(
+            format_line(16, b"fox\x00\x01\xff"),
+            "00000010: 666f 7800 01ff                           fox...\n"
+        );
+    }
+
+    #[test]
+    fn test_is_binary() {
+        assert!(!is_binary(b""));
+        assert!(!is_binary("Émile Zola\n".as_bytes()));
+        assert!(is_binary(b"ELF\x00\x01"));
+    }
+}
@@ -0,0 +1,111 @@
+use anyhow::Error;
+use walkdir::WalkDir;
+
+/// An input to read, or the path that could not be expanded and why.
+pub type Input = Result<String, (String, Error)>;
+
+/// Expands the FILE arguments into the inputs to read, in order.
+///
+/// With `glob`, arguments holding wildcards are matched against the file
+/// system in sorted order; a pattern matching nothing is kept as is, so that
+/// opening it reports the missing file. With `recursive`, directories are
+/// replaced by every regular file under them, sorted by name.
+pub fn expand(files: &[String], glob: bool, recursive: bool) -> Vec<Input> {
+    files
+        .iter()
+        .flat_map(|file| {
+            if glob && is_pattern(file) {
+                expand_glob(file)
+            } else {
+                vec![Ok(file.clone())]
+            }
+        })
+        .flat_map(|input| match input {
+            Ok(path) if recursive && path != "-" => walk(path),
+            input => vec![input],
+        })
+        .collect()
+}
+
+fn is_pattern(file: &str) -> bool {
+    file.contains(['*', '?', '['])
+}
+
+fn expand_glob(pattern: &str) -> Vec<Input> {
+    let paths = match glob::glob(pattern) {
+        Err(e) => return vec![Err((pattern.to_string(), e.into()))],
+        Ok(paths) => paths,
+    };
+    let inputs: Vec<_> = paths
+        .map(|path| match path {
+            Ok(path) => Ok(path.display().to_string()),
+            Err(e) => Err((e.path().display().to_string(), e.into_error().into())),
+        })
+        .collect();
+    if inputs.is_empty() {
+        return vec![Ok(pattern.to_string())];
+    }
+    inputs
+}
+
+/// Lists the regular files under `path`; a plain file stands for itself.
+fn walk(path: String) -> Vec<Input> {
+    WalkDir::new(&path)
+        .sort_by_file_name()
+        .into_iter()
+        .filter_map(|entry| match entry {
+            Ok(entry) if entry.path().is_file() => Some(Ok(entry.path().display().to_string())),
+            Ok(_) => None,
+            Err(e) => {
+                let at = e.path().map_or(path.clone(), |p| p.display().to_string());
+                Some(Err((at, e.into())))
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    fn expanded(files: &[&str], glob: bool, recursive: bool) -> Vec<String> {
+        let files: Vec<_> = files.iter().map(|f| f.to_string()).collect();
+        expand(&files, glob, recursive)
+            .into_iter()
+            .map(|input| input.unwrap_or_else(|(path, e)| format!("error {path}: {e}")))
+            .collect()
+    }
+
+    #[test]
+    fn test_expand_glob() {
+        assert_eq!(
+            expanded(&["tests/inputs/accents.utf16*.txt", "-"], true, false),
+            [
+                "tests/inputs/accents.utf16be.txt",
+                "tests/inputs/accents.utf16le.txt",
+                "-"
+            ]
+        );
+        assert_eq!(
+            expanded(&["tests/inputs/*.nope"], true, false),
+            ["tests/inputs/*.nope"]
+        );
+        assert_eq!(
+            expanded(&["tests/inputs/*.bin"], false, false),
+            ["tests/inputs/*.bin"]
+        );
+        assert!(expanded(&["tests/["], true, false)[0].starts_with("error tests/[: "));
+    }
+
+    #[test]
+    fn test_expand_recursive() {
+        let files = expanded(&["tests/inputs", "tests/cli.rs"], false, true);
+        assert_eq!(files[0], "tests/inputs/accents.latin1.txt");
+        assert_eq!(files.last().unwrap(), "tests/cli.rs");
+        assert!(files.contains(&"tests/inputs/the-bustle.txt.zst".to_string()));
+        let walked = &files[..files.len() - 1];
+        assert!(walked.windows(2).all(|w| w[0] < w[1]));
+    }
+}
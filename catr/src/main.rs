@@ -1,60 +1,583 @@
 use std::{
     fs::File,
-    io::{self, BufRead, BufReader},
-    process,
+    io::{self, BufRead, BufReader, IsTerminal, Read, Write},
+    process, thread,
+    time::Duration,
 };
 
-use anyhow::Result;
-use clap::{arg, Command};
+use anyhow::{anyhow, Result};
+use clap::{arg, value_parser, ArgAction, Command, ValueEnum};
+use compression::{decompress, Decompress};
+use encoding::{transcode, Transcoding};
+use encoding_rs::{Encoding, UTF_8};
+use follow::Follower;
+use output::Output;
+use serde_json::json;
+
+mod compression;
+mod encoding;
+mod follow;
+mod hex;
+mod inputs;
+mod output;
+mod pretty;
 
 fn main() {
-    if let Err(e) = run(get_args()) {
-        eprintln!("{e}");
-        process::exit(1);
+    match run(get_args()) {
+        // Like GNU cat, say nothing when whoever reads the output stops early
+        Err(e) if is_broken_pipe(&e) => {}
+        Err(e) => {
+            eprintln!("{e}");
+            process::exit(1);
+        }
+        Ok(failures) if !failures.is_empty() => process::exit(1),
+        Ok(_) => {}
     }
 }
 
-fn catr(buff: Box<dyn BufRead>, number_lines: bool, number_non_blank: bool) {
-    let mut num = 0;
-    for (i, l) in buff.lines().enumerate() {
-        let line = l.unwrap();
-        if number_lines {
-            println!("{:>6}\t{}", i + 1, line);
-        } else if number_non_blank {
-            if line.is_empty() {
-                println!();
-            } else {
-                num += 1;
-                println!("{num:>6}\t{line}")
+fn is_broken_pipe(error: &anyhow::Error) -> bool {
+    error
+        .downcast_ref::<io::Error>()
+        .is_some_and(|error| error.kind() == io::ErrorKind::BrokenPipe)
+}
+
+/// Size of the read buffer; large enough to keep the plain copy syscall-light.
+const BUFFER_SIZE: usize = 128 * 1024;
+
+/// Line state carried over from one input to the next, so that numbering and
+/// blank squeezing continue across files like in GNU cat.
+#[derive(Debug, Default)]
+struct State {
+    num: usize,
+    prev_blank: bool,
+    /// The previous input ended in the middle of a line.
+    partial: bool,
+}
+
+fn catr(
+    mut buff: impl BufRead,
+    out: &mut impl Write,
+    args: &Args,
+    state: &mut State,
+) -> Result<()> {
+    if args.hex {
+        hex::dump(buff, out)?;
+        return Ok(());
+    }
+    refuse_binary(&mut buff, args)?;
+    if args.is_plain() {
+        loop {
+            out.flush()?;
+            let bytes = buff.fill_buf()?;
+            let Some(&last) = bytes.last() else {
+                return Ok(());
+            };
+            out.write_all(bytes)?;
+            state.partial = last != b'\n';
+            let len = bytes.len();
+            buff.consume(len);
+        }
+    }
+    if args.ranges.iter().any(LineRange::is_from_end) {
+        let mut bytes = Vec::new();
+        buff.read_to_end(&mut bytes)?;
+        let total = count_lines(&bytes);
+        return cat_lines(bytes.as_slice(), Some(total), out, args, state);
+    }
+    cat_lines(buff, None, out, args, state)
+}
+
+/// Fails on a binary input that would go raw to a terminal.
+fn refuse_binary(buff: &mut impl BufRead, args: &Args) -> Result<()> {
+    if args.refuse_binary && hex::is_binary(buff.fill_buf()?) {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "binary file detected; use --hex to view it or --force to print it anyway",
+        )
+        .into());
+    }
+    Ok(())
+}
+
+/// Prints the input line by line; `total` is its number of lines, when known.
+fn cat_lines(
+    buff: impl BufRead,
+    total: Option<usize>,
+    out: &mut impl Write,
+    args: &Args,
+    state: &mut State,
+) -> Result<()> {
+    let mut buff = Drained::new(buff);
+    let mut line = Vec::new();
+    let mut line_no = 0;
+    loop {
+        line.clear();
+        if buff.is_drained() {
+            // Like GNU cat, flush before a read that may block, so that
+            // `tail -f log | catr -n` keeps up with the log
+            out.flush()?;
+        }
+        if buff.read_until(b'\n', &mut line)? == 0 {
+            break;
+        }
+        line_no += 1;
+        let continued = state.partial;
+        state.partial = !line.ends_with(b"\n");
+        let blank = !continued && line == b"\n";
+        let numbered = !continued && (args.number_lines || (args.number_non_blank && !blank));
+        if !args.ranges.is_empty() && !args.ranges.iter().any(|r| r.contains(line_no, total)) {
+            // Lines left out of the ranges keep their number
+            if numbered {
+                state.num += 1;
             }
+            continue;
+        }
+        if args.squeeze_blank && blank && state.prev_blank {
+            continue;
+        }
+        state.prev_blank = blank;
+        if numbered {
+            write!(out, "{}", args.number_format.format(state.num)?)?;
+            state.num += 1;
+        }
+        if args.show_nonprinting || args.show_ends || args.show_tabs {
+            out.write_all(&visible(&line, args))?;
         } else {
-            println!("{line}");
+            out.write_all(&line)?;
+        }
+    }
+    Ok(())
+}
+
+/// A reader that knows when everything it buffered has been consumed, which
+/// is when the next read may have to wait for more input.
+struct Drained<R> {
+    inner: R,
+    available: usize,
+}
+
+impl<R: BufRead> Drained<R> {
+    fn new(inner: R) -> Self {
+        Drained {
+            inner,
+            available: 0,
+        }
+    }
+
+    fn is_drained(&self) -> bool {
+        self.available == 0
+    }
+}
+
+impl<R: BufRead> Read for Drained<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let len = self.fill_buf()?.read(buf)?;
+        self.consume(len);
+        Ok(len)
+    }
+}
+
+impl<R: BufRead> BufRead for Drained<R> {
+    fn fill_buf(&mut self) -> io::Result<&[u8]> {
+        let bytes = self.inner.fill_buf()?;
+        self.available = bytes.len();
+        Ok(bytes)
+    }
+
+    fn consume(&mut self, amt: usize) {
+        self.available = self.available.saturating_sub(amt);
+        self.inner.consume(amt);
+    }
+}
+
+fn count_lines(bytes: &[u8]) -> usize {
+    let newlines = bytes.iter().filter(|&&b| b == b'\n').count();
+    match bytes.last() {
+        Some(&last) if last != b'\n' => newlines + 1,
+        _ => newlines,
+    }
+}
+
+/// A `START:END` range of line numbers, both ends included.
+///
+/// A missing end is open, and a negative one counts from the end of the
+/// input, `-1` being its last line.
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct LineRange {
+    start: Option<i64>,
+    end: Option<i64>,
+}
+
+impl LineRange {
+    fn is_from_end(&self) -> bool {
+        self.start.is_some_and(i64::is_negative) || self.end.is_some_and(i64::is_negative)
+    }
+
+    /// Whether the 1-based `line` falls in the range of an input of `total` lines.
+    ///
+    /// `total` is only needed, and only looked at, for ranges counting from the end.
+    fn contains(&self, line: usize, total: Option<usize>) -> bool {
+        let resolve = |bound: i64| {
+            if bound < 0 {
+                total.unwrap_or(0) as i64 + 1 + bound
+            } else {
+                bound
+            }
+        };
+        let line = line as i64;
+        self.start.is_none_or(|start| resolve(start) <= line)
+            && self.end.is_none_or(|end| line <= resolve(end))
+    }
+}
+
+fn parse_line_range(value: &str) -> Result<LineRange, String> {
+    let bound = |bound: &str| -> Result<Option<i64>, String> {
+        match bound {
+            "" => Ok(None),
+            _ => match bound.parse() {
+                Ok(0) | Err(_) => Err(format!("illegal line number: \"{bound}\"")),
+                Ok(num) => Ok(Some(num)),
+            },
+        }
+    };
+    let (start, end) = match value.split_once(':') {
+        Some((start, end)) => (bound(start)?, bound(end)?),
+        None if !value.is_empty() => (bound(value)?, bound(value)?),
+        None => return Err("empty line range".to_string()),
+    };
+    Ok(LineRange { start, end })
+}
+
+/// How the line numbers of `-n` and `-b` are laid out, in the spirit of nl.
+#[derive(Debug)]
+struct NumberFormat {
+    width: usize,
+    separator: String,
+    start: i64,
+    increment: i64,
+    justify: Justify,
+}
+
+impl NumberFormat {
+    /// Formats the number of the `index`-th numbered line, separator included.
+    ///
+    /// Fails once the number no longer fits in an `i64`.
+    fn format(&self, index: usize) -> Result<String> {
+        let num = i64::try_from(index)
+            .ok()
+            .and_then(|index| self.increment.checked_mul(index))
+            .and_then(|offset| self.start.checked_add(offset))
+            .ok_or_else(|| anyhow!("line number out of range"))?;
+        let (width, sep) = (self.width, &self.separator);
+        Ok(match self.justify {
+            Justify::Left => format!("{num:<width$}{sep}"),
+            Justify::Right => format!("{num:>width$}{sep}"),
+            Justify::Zero => format!("{num:0width$}{sep}"),
+        })
+    }
+}
+
+/// Line number justification, named after the formats of `nl -n`.
+#[derive(Clone, Copy, Debug, PartialEq, ValueEnum)]
+enum Justify {
+    /// Left justified, no leading zeros
+    #[value(name = "ln")]
+    Left,
+    /// Right justified, no leading zeros
+    #[value(name = "rn")]
+    Right,
+    /// Right justified, leading zeros
+    #[value(name = "rz")]
+    Zero,
+}
+
+/// Renders a raw line using the `^X` and `M-X` notation of GNU cat.
+///
+/// Tabs, line feeds and carriage returns are only escaped when the matching
+/// flag asks for it.
+fn visible(line: &[u8], args: &Args) -> Vec<u8> {
+    let (content, newline) = match line.strip_suffix(b"\n") {
+        Some(content) => (content, true),
+        None => (line, false),
+    };
+    let mut out = Vec::with_capacity(line.len() + 2);
+    for (i, &byte) in content.iter().enumerate() {
+        match byte {
+            b'\t' if args.show_tabs => out.extend_from_slice(b"^I"),
+            b'\t' => out.push(byte),
+            b'\r' if args.show_ends && newline && i + 1 == content.len() => {
+                out.extend_from_slice(b"^M")
+            }
+            _ if args.show_nonprinting => push_nonprinting(byte, &mut out),
+            _ => out.push(byte),
+        }
+    }
+    if newline {
+        if args.show_ends {
+            out.push(b'$');
         }
+        out.push(b'\n');
+    }
+    out
+}
+
+fn push_nonprinting(byte: u8, out: &mut Vec<u8>) {
+    let byte = if byte >= 128 {
+        out.extend_from_slice(b"M-");
+        byte - 128
+    } else {
+        byte
+    };
+    match byte {
+        0..=31 => out.extend_from_slice(&[b'^', byte + 64]),
+        127 => out.extend_from_slice(b"^?"),
+        _ => out.push(byte),
     }
 }
 
-fn run(args: Args) -> Result<()> {
-    for filename in args.files {
-        match open(&filename) {
-            Err(e) => eprintln!("Failed to open {filename}: {e}"),
-            Ok(buff) => catr(buff, args.number_lines, args.number_non_blank),
+/// An input that could not be opened or read.
+#[derive(Debug)]
+struct Failure {
+    path: String,
+    kind: io::ErrorKind,
+    message: String,
+}
+
+impl Failure {
+    fn new(path: &str, error: &anyhow::Error) -> Self {
+        Failure {
+            path: path.to_string(),
+            kind: error
+                .downcast_ref::<io::Error>()
+                .map_or(io::ErrorKind::Other, io::Error::kind),
+            message: error.to_string(),
         }
     }
+}
+
+/// Concatenates every input, returning the ones that failed.
+///
+/// A failing input does not stop the others from being printed, except when
+/// stdout itself is gone. With `--follow` this never returns once every input
+/// has been printed.
+fn run(args: Args) -> Result<Vec<Failure>> {
+    let mut failures = Vec::new();
+    let mut followers = Vec::new();
+    let inputs = inputs::expand(&args.files, args.glob, args.recursive);
+    let paths: Vec<_> = inputs.iter().flatten().cloned().collect();
+    let mut out = Output::create(args.output.as_deref(), &paths)?;
+    let mut printer = if args.pretty && args.output.is_none() {
+        pretty::terminal(args.paging)
+    } else {
+        None
+    };
+    let mut state = State::default();
+    let mut headers = 0;
+    for input in inputs {
+        if args.number_per_file {
+            // Only the numbering restarts, the output may still be mid-line
+            state = State {
+                partial: state.partial,
+                ..State::default()
+            };
+        }
+        let (filename, action, result) = match input {
+            Err((path, e)) => (path, "open", Err(e)),
+            Ok(filename) if args.follow && filename != "-" => match Follower::open(&filename) {
+                Err(e) => (filename, "open", Err(e.into())),
+                Ok(follower) => {
+                    let result = header(&mut out, &filename, &args, &mut headers, &mut state)
+                        .and_then(|()| catr(follower.reader(), &mut out, &args, &mut state))
+                        .map(|()| followers.push(follower));
+                    (filename, "read", result)
+                }
+            },
+            Ok(filename) if printer.is_none() && args.is_verbatim() => {
+                let (action, result) = copy_verbatim(&filename, &mut out);
+                (filename, action, result)
+            }
+            Ok(filename) => match open(&filename, args.decompress, args.transcoding) {
+                Err(e) => (filename, "open", Err(e)),
+                Ok(mut buff) => {
+                    let result = match printer.as_mut() {
+                        Some(printer) => refuse_binary(&mut buff, &args)
+                            .and_then(|()| printer.print(&filename, buff)),
+                        None => header(&mut out, &filename, &args, &mut headers, &mut state)
+                            .and_then(|()| catr(buff, &mut out, &args, &mut state)),
+                    };
+                    (filename, "read", result)
+                }
+            },
+        };
+        if let Err(e) = result {
+            let failure = Failure::new(&filename, &e);
+            if failure.kind == io::ErrorKind::BrokenPipe {
+                return Err(e);
+            }
+            if args.errors == ErrorFormat::Text {
+                eprintln!("Failed to {action} {filename}: {e}");
+            }
+            failures.push(failure);
+        }
+    }
+    if let Some(printer) = printer {
+        printer.finish()?;
+    }
+    if args.errors == ErrorFormat::Json && !failures.is_empty() {
+        eprintln!("{}", errors_report(&failures));
+    }
+    if !followers.is_empty() {
+        follow(followers, &mut out, &args, state)?;
+    }
+    out.commit()?;
+    Ok(failures)
+}
+
+/// Copies an input that needs no processing at all, returning what failed if
+/// it did: opening or reading it.
+fn copy_verbatim(filename: &str, out: &mut Output) -> (&'static str, Result<()>) {
+    let result = match filename {
+        "-" => out.copy_from(&mut io::stdin().lock()),
+        _ => match File::open(filename) {
+            Err(e) => return ("open", Err(e.into())),
+            Ok(mut file) => out.copy_from(&mut file),
+        },
+    };
+    ("read", result.map(|_| ()).map_err(Into::into))
+}
+
+/// Writes the `==> FILE <==` header that precedes each input with `--headers`.
+///
+/// Headers after the first are set apart from the previous input by a blank line.
+fn header(
+    out: &mut impl Write,
+    filename: &str,
+    args: &Args,
+    count: &mut usize,
+    state: &mut State,
+) -> Result<()> {
+    if !args.headers {
+        return Ok(());
+    }
+    // A previous input without a final newline still has its last line to end
+    let newline = if state.partial { "\n" } else { "" };
+    let blank = if *count > 0 { "\n" } else { "" };
+    writeln!(out, "{newline}{blank}==> {filename} <==")?;
+    *count += 1;
+    state.partial = false;
     Ok(())
 }
 
-fn open(filename: &str) -> Result<Box<dyn BufRead>> {
-    match filename {
-        "-" => Ok(Box::new(BufReader::new(io::stdin()))),
-        _ => Ok(Box::new(BufReader::new(File::open(filename)?))),
+/// Keeps printing whatever is appended to the followed files, forever.
+///
+/// The line state is shared with the initial pass, so numbering carries on.
+fn follow(
+    mut followers: Vec<Follower>,
+    out: &mut impl Write,
+    args: &Args,
+    mut state: State,
+) -> Result<()> {
+    loop {
+        out.flush()?;
+        thread::sleep(args.sleep_interval);
+        for follower in &mut followers {
+            if let Some(reader) = follower.poll()? {
+                catr(reader, out, args, &mut state)?;
+            }
+        }
     }
 }
 
+fn errors_report(failures: &[Failure]) -> serde_json::Value {
+    let errors: Vec<_> = failures
+        .iter()
+        .map(|f| {
+            json!({
+                "path": f.path,
+                "kind": format!("{:?}", f.kind),
+                "message": f.message,
+            })
+        })
+        .collect();
+    json!({ "errors": errors })
+}
+
+fn open(
+    filename: &str,
+    when: Decompress,
+    transcoding: Option<Transcoding>,
+) -> Result<Box<dyn BufRead>> {
+    let buff: Box<dyn BufRead> = match filename {
+        "-" => Box::new(BufReader::with_capacity(BUFFER_SIZE, io::stdin())),
+        _ => Box::new(BufReader::with_capacity(BUFFER_SIZE, File::open(filename)?)),
+    };
+    let buff = decompress(buff, when)?;
+    Ok(match transcoding {
+        Some(transcoding) => transcode(buff, transcoding),
+        None => buff,
+    })
+}
+
+/// How failed inputs are reported on stderr.
+#[derive(Clone, Copy, Debug, PartialEq, ValueEnum)]
+enum ErrorFormat {
+    /// One message per failed input, as it happens
+    Text,
+    /// A single JSON summary once every input has been processed
+    Json,
+}
+
 #[derive(Debug)]
 struct Args {
     files: Vec<String>,
     number_lines: bool,
     number_non_blank: bool,
+    number_per_file: bool,
+    number_format: NumberFormat,
+    show_nonprinting: bool,
+    show_ends: bool,
+    show_tabs: bool,
+    squeeze_blank: bool,
+    errors: ErrorFormat,
+    decompress: Decompress,
+    follow: bool,
+    sleep_interval: Duration,
+    pretty: bool,
+    paging: bool,
+    ranges: Vec<LineRange>,
+    transcoding: Option<Transcoding>,
+    output: Option<String>,
+    hex: bool,
+    glob: bool,
+    recursive: bool,
+    headers: bool,
+    /// Binary inputs are not dumped raw to a terminal.
+    refuse_binary: bool,
+}
+
+impl Args {
+    /// Whether the input can be copied verbatim, without looking at lines.
+    fn is_plain(&self) -> bool {
+        !(self.number_lines
+            || self.number_non_blank
+            || self.show_nonprinting
+            || self.show_ends
+            || self.show_tabs
+            || self.squeeze_blank
+            || !self.ranges.is_empty())
+    }
+
+    /// Whether inputs can go straight from the file to the output, without
+    /// even being looked at.
+    fn is_verbatim(&self) -> bool {
+        self.is_plain()
+            && !self.hex
+            && !self.refuse_binary
+            && self.decompress == Decompress::Never
+            && self.transcoding.is_none()
+            // Headers need to know whether the previous input ended mid-line
+            && !self.headers
+    }
 }
 
 fn get_args() -> Args {
@@ -66,13 +589,223 @@ fn get_args() -> Args {
             arg!(-n --number "Print line numbers").conflicts_with("number-nonblank"),
             arg!(-b --"number-nonblank" "Print line numbers for non-blank lines")
                 .conflicts_with("number"),
+            arg!(--"number-per-file" "Restart line numbering at each input file"),
+            arg!(--"number-width" <WIDTH> "Use WIDTH columns for line numbers")
+                .value_parser(value_parser!(usize))
+                .default_value("6"),
+            arg!(--"number-separator" <SEP> "Add SEP after line numbers").default_value("\t"),
+            arg!(--"number-start" <NUMBER> "First line number")
+                .value_parser(value_parser!(i64))
+                .allow_negative_numbers(true)
+                .default_value("1"),
+            arg!(--"number-increment" <NUMBER> "Line number increment")
+                .value_parser(value_parser!(i64))
+                .allow_negative_numbers(true)
+                .default_value("1"),
+            arg!(--"number-format" <FORMAT> "Line number justification")
+                .value_parser(value_parser!(Justify))
+                .default_value("rn"),
+            arg!(-s --"squeeze-blank" "Suppress repeated empty output lines"),
+            arg!(-A --"show-all" "Equivalent to -vET"),
+            arg!(e: -e "Equivalent to -vE"),
+            arg!(-E --"show-ends" "Display $ at end of each line"),
+            arg!(t: -t "Equivalent to -vT"),
+            arg!(-T --"show-tabs" "Display TAB characters as ^I"),
+            arg!(-v --"show-nonprinting" "Use ^ and M- notation, except for LFD and TAB"),
+            arg!(-z --decompress [WHEN] "Decompress gzip, bzip2, xz and zstd inputs; -z alone means auto")
+                .value_parser(value_parser!(Decompress))
+                .require_equals(true)
+                .default_value("never")
+                .default_missing_value("auto"),
+            arg!(-f --follow "Keep printing data appended to the files")
+                .conflicts_with("decompress"),
+            arg!(--"sleep-interval" <SECONDS> "Seconds to wait between checks for new data")
+                .value_parser(parse_seconds)
+                .requires("follow")
+                .default_value("1.0"),
+            arg!(--pretty "Show headers, a line number gutter, syntax highlighting and git changes on a terminal")
+                .conflicts_with_all([
+                    "number",
+                    "number-nonblank",
+                    "number-per-file",
+                    "number-width",
+                    "number-separator",
+                    "number-start",
+                    "number-increment",
+                    "number-format",
+                    "show-all",
+                    "e",
+                    "show-ends",
+                    "t",
+                    "show-tabs",
+                    "show-nonprinting",
+                    "squeeze-blank",
+                    "headers",
+                    "follow",
+                ]),
+            arg!(--"no-pager" "Do not page --pretty output").requires("pretty"),
+            arg!(-r --range <RANGE> "Only print lines in START:END, counting from the end when negative")
+                .value_parser(parse_line_range)
+                .value_delimiter(',')
+                .action(ArgAction::Append)
+                .allow_hyphen_values(true)
+                .conflicts_with_all(["pretty", "follow"]),
+            arg!(--"from-encoding" <ENCODING> "Encoding of the inputs, or auto to detect it from the BOM")
+                .value_parser(encoding::parse_from_encoding)
+                .conflicts_with("follow"),
+            arg!(--"to-encoding" <ENCODING> "Encoding of the output [default: UTF-8]")
+                .value_parser(encoding::parse_to_encoding)
+                .conflicts_with("follow"),
+            arg!(-o --output <PATH> "Write to PATH instead of stdout, replacing it atomically")
+                .conflicts_with("follow"),
+            arg!(-x --hex "Show the input as an xxd-style hex dump").conflicts_with_all([
+                "number",
+                "number-nonblank",
+                "show-all",
+                "e",
+                "show-ends",
+                "t",
+                "show-tabs",
+                "show-nonprinting",
+                "squeeze-blank",
+                "range",
+                "pretty",
+                "follow",
+            ]),
+            arg!(--force "Print binary inputs even when writing to a terminal"),
+            arg!(--glob "Expand wildcards in FILE arguments, for shells that do not"),
+            arg!(-R --recursive "Read every regular file under directories, in sorted order"),
+            arg!(--headers "Print a ==> FILE <== header before each input"),
+            arg!(--errors <FORMAT> "How to report inputs that could not be read")
+                .value_parser(value_parser!(ErrorFormat))
+                .default_value("text"),
             arg!([FILE] ... "Input file(s) to read").default_value("-"),
         ])
         .get_matches();
 
+    let show_all = matches.get_flag("show-all");
+    let show_ends_nonprinting = matches.get_flag("e");
+    let show_tabs_nonprinting = matches.get_flag("t");
+
+    let show_nonprinting = matches.get_flag("show-nonprinting")
+        || show_all
+        || show_ends_nonprinting
+        || show_tabs_nonprinting;
+
+    let from_encoding = matches.get_one::<Option<&'static Encoding>>("from-encoding");
+    let to_encoding = matches.get_one::<&'static Encoding>("to-encoding");
+    let transcoding = (from_encoding.is_some() || to_encoding.is_some()).then(|| Transcoding {
+        from: from_encoding.copied().flatten(),
+        to: to_encoding.copied().unwrap_or(UTF_8),
+    });
+
     Args {
         files: matches.get_many("FILE").unwrap().cloned().collect(),
         number_lines: matches.get_flag("number"),
         number_non_blank: matches.get_flag("number-nonblank"),
+        number_per_file: matches.get_flag("number-per-file"),
+        number_format: NumberFormat {
+            width: *matches.get_one("number-width").unwrap(),
+            separator: matches
+                .get_one::<String>("number-separator")
+                .unwrap()
+                .clone(),
+            start: *matches.get_one("number-start").unwrap(),
+            increment: *matches.get_one("number-increment").unwrap(),
+            justify: *matches.get_one("number-format").unwrap(),
+        },
+        show_nonprinting,
+        show_ends: matches.get_flag("show-ends") || show_all || show_ends_nonprinting,
+        show_tabs: matches.get_flag("show-tabs") || show_all || show_tabs_nonprinting,
+        squeeze_blank: matches.get_flag("squeeze-blank"),
+        errors: *matches.get_one("errors").unwrap(),
+        decompress: *matches.get_one("decompress").unwrap(),
+        follow: matches.get_flag("follow"),
+        sleep_interval: *matches.get_one("sleep-interval").unwrap(),
+        pretty: matches.get_flag("pretty"),
+        paging: !matches.get_flag("no-pager"),
+        ranges: matches
+            .get_many("range")
+            .map(|ranges| ranges.copied().collect())
+            .unwrap_or_default(),
+        transcoding,
+        output: matches.get_one("output").cloned(),
+        hex: matches.get_flag("hex"),
+        glob: matches.get_flag("glob"),
+        recursive: matches.get_flag("recursive"),
+        headers: matches.get_flag("headers"),
+        // ^ and M- notation is safe on a terminal, raw binary is not
+        refuse_binary: !matches.get_flag("force")
+            && !show_nonprinting
+            && !matches.contains_id("output")
+            && io::stdout().is_terminal(),
+    }
+}
+
+fn parse_seconds(value: &str) -> Result<Duration, String> {
+    let seconds: f64 = value
+        .parse()
+        .map_err(|_| format!("invalid number: {value}"))?;
+    Duration::try_from_secs_f64(seconds).map_err(|e| e.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    #[test]
+    fn test_parse_line_range() {
+        let range = |start, end| Ok(LineRange { start, end });
+        assert_eq!(parse_line_range("100:200"), range(Some(100), Some(200)));
+        assert_eq!(parse_line_range("7"), range(Some(7), Some(7)));
+        assert_eq!(parse_line_range("3:"), range(Some(3), None));
+        assert_eq!(parse_line_range(":5"), range(None, Some(5)));
+        assert_eq!(parse_line_range(":"), range(None, None));
+        assert_eq!(parse_line_range("-10:"), range(Some(-10), None));
+        assert_eq!(parse_line_range("2:-2"), range(Some(2), Some(-2)));
+
+        assert_eq!(
+            parse_line_range("0:3").unwrap_err(),
+            r#"illegal line number: "0""#
+        );
+        assert_eq!(
+            parse_line_range("1:a").unwrap_err(),
+            r#"illegal line number: "a""#
+        );
+        assert_eq!(
+            parse_line_range("1:2:3").unwrap_err(),
+            r#"illegal line number: "2:3""#
+        );
+        assert_eq!(parse_line_range("").unwrap_err(), "empty line range");
+    }
+
+    #[test]
+    fn test_line_range_contains() {
+        let range = parse_line_range("2:4").unwrap();
+        assert!(!range.contains(1, None));
+        assert!(range.contains(2, None));
+        assert!(range.contains(4, None));
+        assert!(!range.contains(5, None));
+
+        let last_three = parse_line_range("-3:").unwrap();
+        assert!(last_three.is_from_end());
+        assert!(!last_three.contains(7, Some(10)));
+        assert!(last_three.contains(8, Some(10)));
+        assert!(last_three.contains(10, Some(10)));
+
+        let all_but_last = parse_line_range(":-2").unwrap();
+        assert!(all_but_last.contains(1, Some(10)));
+        assert!(all_but_last.contains(9, Some(10)));
+        assert!(!all_but_last.contains(10, Some(10)));
+    }
+
+    #[test]
+    fn test_count_lines() {
+        assert_eq!(count_lines(b""), 0);
+        assert_eq!(count_lines(b"\n"), 1);
+        assert_eq!(count_lines(b"a\nb\n"), 2);
+        assert_eq!(count_lines(b"a\nb"), 2);
     }
 }
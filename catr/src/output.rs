@@ -0,0 +1,140 @@
+use std::{
+    fs::{self, Metadata, Permissions},
+    io::{self, BufWriter, IsTerminal, Read, StdoutLock, Write},
+    path::{Path, PathBuf},
+};
+
+use anyhow::{bail, Result};
+use tempfile::{Builder, NamedTempFile};
+
+use crate::BUFFER_SIZE;
+
+/// Where the concatenation goes.
+///
+/// A file output is written to a temporary file next to it, which only
+/// replaces the file once everything has been written; an error along the
+/// way leaves the previous contents alone.
+///
+/// Writes are buffered, so that line by line output does not cost a system
+/// call (and a lock of stdout) per line; only a terminal is left unbuffered.
+pub enum Output {
+    Stdout(BufWriter<StdoutLock<'static>>),
+    File {
+        temp: BufWriter<NamedTempFile>,
+        path: PathBuf,
+    },
+}
+
+impl Output {
+    /// Opens the output, refusing an output that is also one of the `inputs`
+    /// (the `cat a b > a` trap).
+    pub fn create(path: Option<&str>, inputs: &[String]) -> Result<Self> {
+        let Some(path) = path else {
+            let stdout = io::stdout();
+            // A terminal gets no buffer at all, so that output keeps up
+            // with input typed or piped in slowly
+            let capacity = if stdout.is_terminal() { 0 } else { BUFFER_SIZE };
+            return Ok(Output::Stdout(BufWriter::with_capacity(
+                capacity,
+                stdout.lock(),
+            )));
+        };
+        // Replace the file a symlink points to, not the symlink
+        let path = fs::canonicalize(path).unwrap_or_else(|_| PathBuf::from(path));
+        let existing = fs::metadata(&path).ok();
+        if let Some(existing) = &existing {
+            for input in inputs.iter().filter(|input| *input != "-") {
+                if is_output(input, &path, existing) {
+                    bail!("{input}: input file is output");
+                }
+            }
+        }
+        let dir = match path.parent() {
+            Some(dir) if !dir.as_os_str().is_empty() => dir,
+            _ => Path::new("."),
+        };
+        let mut builder = Builder::new();
+        builder.prefix(".catr");
+        if let Some(permissions) = existing
+            .map(|meta| meta.permissions())
+            .or_else(new_permissions)
+        {
+            builder.permissions(permissions);
+        }
+        let temp = BufWriter::with_capacity(BUFFER_SIZE, builder.tempfile_in(dir)?);
+        Ok(Output::File { temp, path })
+    }
+
+    /// Copies `input` as is, bypassing the buffer.
+    ///
+    /// For a file or stdin on Linux, the standard library hands the copy to
+    /// the kernel (`copy_file_range`, `sendfile` or `splice`), so the data
+    /// never goes through user space.
+    pub fn copy_from(&mut self, input: &mut impl Read) -> io::Result<u64> {
+        self.flush()?;
+        match self {
+            Output::Stdout(stdout) => io::copy(input, stdout.get_mut()),
+            Output::File { temp, .. } => io::copy(input, temp.get_mut().as_file_mut()),
+        }
+    }
+
+    /// Moves a file output into place.
+    pub fn commit(self) -> Result<()> {
+        match self {
+            Output::Stdout(mut stdout) => stdout.flush()?,
+            Output::File { temp, path } => {
+                let temp = temp.into_inner().map_err(|e| e.into_error())?;
+                temp.persist(path)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Whether `input` is the existing output file, hard links included.
+#[cfg(unix)]
+fn is_output(input: &str, _output: &Path, existing: &Metadata) -> bool {
+    fs::metadata(input).is_ok_and(|meta| crate::follow::is_same_file(&meta, existing))
+}
+
+#[cfg(not(unix))]
+fn is_output(input: &str, output: &Path, _existing: &Metadata) -> bool {
+    fs::canonicalize(input).is_ok_and(|input| input == output)
+}
+
+/// Permissions for a new output, which would otherwise be as private as the
+/// temporary file it starts as.
+#[cfg(unix)]
+fn new_permissions() -> Option<Permissions> {
+    use std::os::unix::fs::PermissionsExt;
+
+    Some(Permissions::from_mode(0o644))
+}
+
+#[cfg(not(unix))]
+fn new_permissions() -> Option<Permissions> {
+    None
+}
+
+impl Write for Output {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self {
+            Output::Stdout(stdout) => stdout.write(buf),
+            Output::File { temp, .. } => temp.write(buf),
+        }
+    }
+
+    fn write_all(&mut self, buf: &[u8]) -> io::Result<()> {
+        match self {
+            Output::Stdout(stdout) => stdout.write_all(buf),
+            Output::File { temp, .. } => temp.write_all(buf),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self {
+            Output::Stdout(stdout) => stdout.flush(),
+            Output::File { temp, .. } => temp.flush(),
+        }
+    }
+}
@@ -0,0 +1,278 @@
+use std::{
+    collections::HashMap,
+    env,
+    io::{self, BufRead, IsTerminal, Write},
+    path::Path,
+    process::{Child, Command, Stdio},
+};
+
+use anyhow::Result;
+use syntect::{
+    easy::HighlightLines,
+    highlighting::{Theme, ThemeSet},
+    parsing::{SyntaxReference, SyntaxSet},
+    util::{as_24_bit_terminal_escaped, LinesWithEndings},
+};
+
+const THEME: &str = "base16-ocean.dark";
+const GUTTER_WIDTH: usize = 7;
+const GRAY: &str = "\x1b[38;5;243m";
+const GREEN: &str = "\x1b[32m";
+const YELLOW: &str = "\x1b[33m";
+const RED: &str = "\x1b[31m";
+const RESET: &str = "\x1b[0m";
+
+/// How a line of the working tree differs from the git index.
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Change {
+    Added,
+    Modified,
+    RemovedAbove,
+    RemovedBelow,
+}
+
+impl Change {
+    fn sign(self) -> String {
+        match self {
+            Change::Added => format!("{GREEN}+{RESET}"),
+            Change::Modified => format!("{YELLOW}~{RESET}"),
+            Change::RemovedAbove => format!("{RED}‾{RESET}"),
+            Change::RemovedBelow => format!("{RED}_{RESET}"),
+        }
+    }
+}
+
+/// Prints files the way bat does: a header per file, a gutter with line
+/// numbers and git changes, and syntax highlighting picked by extension.
+pub struct Printer<W: Write> {
+    out: W,
+    pager: Option<Child>,
+    syntaxes: SyntaxSet,
+    theme: Theme,
+    width: usize,
+}
+
+/// Sets up a printer writing to the terminal, through a pager if `paging`.
+///
+/// Returns `None` when stdout is not a terminal, so pipes get plain output.
+pub fn terminal(paging: bool) -> Option<Printer<Box<dyn Write>>> {
+    if !io::stdout().is_terminal() {
+        return None;
+    }
+    let mut pager = if paging { spawn_pager() } else { None };
+    let out: Box<dyn Write> = match pager.as_mut().and_then(|p| p.stdin.take()) {
+        Some(stdin) => Box::new(stdin),
+        None => Box::new(io::stdout()),
+    };
+    let width = env::var("COLUMNS")
+        .ok()
+        .and_then(|c| c.parse().ok())
+        .unwrap_or(80);
+    Some(Printer {
+        pager,
+        ..Printer::new(out, width)
+    })
+}
+
+/// Starts `$PAGER`, or `less` when it is not set.
+fn spawn_pager() -> Option<Child> {
+    let pager = env::var("PAGER").unwrap_or_else(|_| "less -RFX".to_string());
+    let mut words = pager.split_whitespace();
+    let program = words.next()?;
+    Command::new(program)
+        .args(words)
+        .stdin(Stdio::piped())
+        .spawn()
+        .ok()
+}
+
+impl<W: Write> Printer<W> {
+    pub fn new(out: W, width: usize) -> Self {
+        let mut themes = ThemeSet::load_defaults().themes;
+        Printer {
+            out,
+            pager: None,
+            syntaxes: SyntaxSet::load_defaults_newlines(),
+            theme: themes.remove(THEME).unwrap_or_default(),
+            width: width.max(GUTTER_WIDTH + 2),
+        }
+    }
+
+    pub fn print(&mut self, filename: &str, mut buff: impl BufRead) -> Result<()> {
+        let mut bytes = Vec::new();
+        buff.read_to_end(&mut bytes)?;
+        let text = String::from_utf8_lossy(&bytes);
+        let changes = git_changes(filename);
+
+        let name = if filename == "-" { "STDIN" } else { filename };
+        self.rule('┬')?;
+        writeln!(self.out, "{GRAY}{:GUTTER_WIDTH$}│{RESET} File: {name}", "")?;
+        self.rule('┼')?;
+        let syntax = find_syntax(&self.syntaxes, filename, &text);
+        let mut highlighter = HighlightLines::new(syntax, &self.theme);
+        for (i, line) in LinesWithEndings::from(&text).enumerate() {
+            let num = i + 1;
+            let sign = changes.get(&num).map_or(" ".to_string(), |c| c.sign());
+            let ranges = highlighter.highlight_line(line, &self.syntaxes)?;
+            write!(self.out, "{GRAY}{num:>4}{RESET} {sign} {GRAY}│{RESET} ")?;
+            let escaped = as_24_bit_terminal_escaped(&ranges, false);
+            writeln!(
+                self.out,
+                "{}{RESET}",
+                escaped.trim_end_matches(['\n', '\r'])
+            )?;
+        }
+        self.rule('┴')?;
+        Ok(())
+    }
+
+    /// Flushes the output and waits for the pager, if any, to be closed.
+    pub fn finish(mut self) -> Result<()> {
+        self.out.flush()?;
+        drop(self.out);
+        if let Some(mut pager) = self.pager.take() {
+            pager.wait()?;
+        }
+        Ok(())
+    }
+
+    fn rule(&mut self, joint: char) -> io::Result<()> {
+        let left = "─".repeat(GUTTER_WIDTH);
+        let right = "─".repeat(self.width - GUTTER_WIDTH - 1);
+        writeln!(self.out, "{GRAY}{left}{joint}{right}{RESET}")
+    }
+}
+
+/// Picks a syntax by extension or file name, then by the first line.
+fn find_syntax<'a>(syntaxes: &'a SyntaxSet, filename: &str, text: &str) -> &'a SyntaxReference {
+    let path = Path::new(filename);
+    path.extension()
+        .or_else(|| path.file_name())
+        .and_then(|ext| syntaxes.find_syntax_by_extension(&ext.to_string_lossy()))
+        .or_else(|| syntaxes.find_syntax_by_first_line(text.lines().next()?))
+        .unwrap_or_else(|| syntaxes.find_syntax_plain_text())
+}
+
+/// Asks git which lines of `filename` differ from the index.
+///
+/// Anything that goes wrong (no git, not a repository, untracked file) just
+/// means no changes are shown.
+fn git_changes(filename: &str) -> HashMap<usize, Change> {
+    let path = Path::new(filename);
+    let (Some(dir), Some(name)) = (path.parent(), path.file_name()) else {
+        return HashMap::new();
+    };
+    let dir = if dir.as_os_str().is_empty() {
+        Path::new(".")
+    } else {
+        dir
+    };
+    Command::new("git")
+        .arg("-C")
+        .arg(dir)
+        .args(["diff", "--no-color", "--no-ext-diff", "--unified=0", "--"])
+        .arg(name)
+        .stderr(Stdio::null())
+        .output()
+        .ok()
+        .filter(|output| output.status.success())
+        .map(|output| parse_diff(&String::from_utf8_lossy(&output.stdout)))
+        .unwrap_or_default()
+}
+
+/// Reads the changed lines out of the hunk headers of a zero-context diff.
+fn parse_diff(diff: &str) -> HashMap<usize, Change> {
+    let mut changes = HashMap::new();
+    for header in diff.lines().filter(|l| l.starts_with("@@ ")) {
+        let mut ranges = header.split(' ').skip(1);
+        let (Some(old), Some(new)) = (ranges.next(), ranges.next()) else {
+            continue;
+        };
+        let (Some((_, removed)), Some((start, added))) = (parse_range(old), parse_range(new))
+        else {
+            continue;
+        };
+        if added == 0 {
+            if start == 0 {
+                changes.insert(1, Change::RemovedAbove);
+            } else {
+                changes.insert(start, Change::RemovedBelow);
+            }
+            continue;
+        }
+        let change = if removed == 0 {
+            Change::Added
+        } else {
+            Change::Modified
+        };
+        for num in start..start + added {
+            changes.insert(num, change);
+        }
+    }
+    changes
+}
+
+/// Parses a `-12,3` or `+12` hunk range into its start and length.
+fn parse_range(range: &str) -> Option<(usize, usize)> {
+    let range = range.get(1..)?;
+    match range.split_once(',') {
+        Some((start, len)) => Some((start.parse().ok()?, len.parse().ok()?)),
+        None => Some((range.parse().ok()?, 1)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    #[test]
+    fn test_parse_diff() {
+        let diff = "\
+diff --git a/fox.txt b/fox.txt
+--- a/fox.txt
++++ b/fox.txt
+@@ -0,0 +1,2 @@
++new
++lines
+@@ -4 +6 @@ fn main() {
+-old
++new
+@@ -9,2 +10,0 @@
+-gone
+-too
+";
+        let changes = parse_diff(diff);
+        let expected = HashMap::from([
+            (1, Change::Added),
+            (2, Change::Added),
+            (6, Change::Modified),
+            (10, Change::RemovedBelow),
+        ]);
+        assert_eq!(changes, expected);
+
+        let changes = parse_diff("@@ -1,3 +0,0 @@\n");
+        assert_eq!(changes, HashMap::from([(1, Change::RemovedAbove)]));
+    }
+
+    #[test]
+    fn test_print() {
+        let mut printer = Printer::new(Vec::new(), 20);
+        let result = printer.print("-", "fn main() {}\nno newline".as_bytes());
+        assert!(result.is_ok());
+
+        let output = String::from_utf8(printer.out).unwrap();
+        let lines: Vec<_> = output.lines().collect();
+        assert_eq!(lines.len(), 6);
+        assert!(lines[1].ends_with("│\x1b[0m File: STDIN"));
+        assert!(lines[3].contains("   1"));
+        assert!(lines[3].contains("main"));
+        assert!(lines[4].contains("   2"));
+        assert!(lines[4].contains("no newline"));
+        assert_eq!(
+            lines[5],
+            format!("{GRAY}{}┴{}{RESET}", "─".repeat(7), "─".repeat(12))
+        );
+    }
+}
@@ -1,4 +1,12 @@
-use std::fs;
+use std::{
+    fs::{self, OpenOptions},
+    io::{BufRead, BufReader, Write},
+    path::Path,
+    process::{self, Stdio},
+    sync::mpsc,
+    thread,
+    time::Duration,
+};
 
 use anyhow::Result;
 use assert_cmd::Command;
@@ -12,6 +20,12 @@ const EMPTY: &str = "tests/inputs/empty.txt";
 const FOX: &str = "tests/inputs/fox.txt";
 const SPIDERS: &str = "tests/inputs/spiders.txt";
 const BUSTLE: &str = "tests/inputs/the-bustle.txt";
+const NONPRINTING: &str = "tests/inputs/nonprinting.txt";
+const BLANKS: &str = "tests/inputs/blanks.txt";
+const CRLF: &str = "tests/inputs/crlf.txt";
+const NO_NEWLINE: &str = "tests/inputs/no-newline.txt";
+const BINARY: &str = "tests/inputs/binary.bin";
+const ACCENTS: &str = "tests/inputs/accents.txt";
 
 #[test]
 fn runs_usage() -> Result<()> {
@@ -44,11 +58,51 @@ fn skips_bad_files() -> Result<()> {
     Command::cargo_bin(CMD)?
         .arg(&filename)
         .assert()
-        .success()
+        .failure()
+        .code(1)
         .stderr(predicate::str::is_match(expected)?);
     Ok(())
 }
 
+#[test]
+fn prints_good_files_around_bad_ones() -> Result<()> {
+    let filename = gen_bad_file();
+    let expected = fs::read_to_string("tests/expected/all.out")?;
+    Command::cargo_bin(CMD)?
+        .args([FOX, &filename, SPIDERS, "tests/inputs", BUSTLE])
+        .assert()
+        .failure()
+        .code(1)
+        .stdout(expected)
+        .stderr(predicate::str::contains(format!(
+            "Failed to open {filename}"
+        )))
+        .stderr(predicate::str::contains("Failed to read tests/inputs"));
+    Ok(())
+}
+
+#[test]
+fn reports_errors_as_json() -> Result<()> {
+    let filename = gen_bad_file();
+    let output = Command::cargo_bin(CMD)?
+        .args(["--errors", "json", &filename, FOX, "tests/inputs"])
+        .output()?;
+    assert_eq!(output.status.code(), Some(1));
+
+    let stderr = String::from_utf8(output.stderr)?;
+    let expected = format!(
+        concat!(
+            r#"{{"errors":["#,
+            r#"{{"kind":"NotFound","message":"No such file or directory (os error 2)","path":"{}"}},"#,
+            r#"{{"kind":"IsADirectory","message":"Is a directory (os error 21)","path":"tests/inputs"}}"#,
+            "]}}\n"
+        ),
+        filename
+    );
+    assert_eq!(stderr, expected);
+    Ok(())
+}
+
 fn run(args: &[&str], expected_file: &str) -> Result<()> {
     let expected = fs::read_to_string(expected_file)?;
     let output = Command::cargo_bin(CMD)?.args(args).output()?;
@@ -59,9 +113,17 @@ fn run(args: &[&str], expected_file: &str) -> Result<()> {
     Ok(())
 }
 
+fn run_bytes(args: &[&str], expected_file: &str) -> Result<()> {
+    let expected = fs::read(expected_file)?;
+    let output = Command::cargo_bin(CMD)?.args(args).output()?;
+    assert!(output.status.success());
+    assert_eq!(output.stdout, expected);
+    Ok(())
+}
+
 fn run_stdin(input_file: &str, args: &[&str], expected_file: &str) -> Result<()> {
     let expected = fs::read_to_string(expected_file)?;
-    let input = fs::read_to_string(input_file)?;
+    let input = fs::read(input_file)?;
     let output = Command::cargo_bin(CMD)?
         .write_stdin(input)
         .args(args)
@@ -169,3 +231,781 @@ fn all_n() -> Result<()> {
 fn all_b() -> Result<()> {
     run(&["-b", FOX, SPIDERS, BUSTLE], "tests/expected/all.b.out")
 }
+
+#[test]
+fn all_n_per_file() -> Result<()> {
+    run(
+        &["-n", "--number-per-file", FOX, SPIDERS, BUSTLE],
+        "tests/expected/all.n.per-file.out",
+    )
+}
+
+#[test]
+fn all_b_per_file() -> Result<()> {
+    run(
+        &["-b", "--number-per-file", FOX, SPIDERS, BUSTLE],
+        "tests/expected/all.b.per-file.out",
+    )
+}
+
+#[test]
+fn all_n_stdin() -> Result<()> {
+    run_stdin(
+        BUSTLE,
+        &["-n", FOX, "-", SPIDERS],
+        "tests/expected/all.n.stdin.out",
+    )
+}
+
+#[test]
+fn bustle_show_all() -> Result<()> {
+    run(&["-A", BUSTLE], "tests/expected/the-bustle.txt.A.out")
+}
+
+#[test]
+fn nonprinting_v() -> Result<()> {
+    run(&["-v", NONPRINTING], "tests/expected/nonprinting.txt.v.out")
+}
+
+#[test]
+fn nonprinting_show_ends() -> Result<()> {
+    run_bytes(&["-E", NONPRINTING], "tests/expected/nonprinting.txt.E.out")
+}
+
+#[test]
+fn nonprinting_show_tabs() -> Result<()> {
+    run_bytes(&["-T", NONPRINTING], "tests/expected/nonprinting.txt.T.out")
+}
+
+#[test]
+fn nonprinting_show_all() -> Result<()> {
+    for flag in ["-A", "--show-all", "-vET"] {
+        run(&[flag, NONPRINTING], "tests/expected/nonprinting.txt.A.out")?;
+    }
+    Ok(())
+}
+
+#[test]
+fn nonprinting_e() -> Result<()> {
+    run(&["-e", NONPRINTING], "tests/expected/nonprinting.txt.e.out")
+}
+
+#[test]
+fn nonprinting_t() -> Result<()> {
+    run(&["-t", NONPRINTING], "tests/expected/nonprinting.txt.t.out")
+}
+
+#[test]
+fn nonprinting_n_show_all() -> Result<()> {
+    run(
+        &["-n", "-A", NONPRINTING],
+        "tests/expected/nonprinting.txt.nA.out",
+    )
+}
+
+#[test]
+fn nonprinting_b_show_tabs() -> Result<()> {
+    run_bytes(
+        &["-b", "-T", NONPRINTING],
+        "tests/expected/nonprinting.txt.bT.out",
+    )
+}
+
+#[test]
+fn blanks_n() -> Result<()> {
+    run(&["-n", BLANKS], "tests/expected/blanks.txt.n.out")
+}
+
+#[test]
+fn blanks_squeeze() -> Result<()> {
+    for flag in ["-s", "--squeeze-blank"] {
+        run(&[flag, BLANKS], "tests/expected/blanks.txt.s.out")?;
+    }
+    Ok(())
+}
+
+#[test]
+fn blanks_squeeze_n() -> Result<()> {
+    run(&["-s", "-n", BLANKS], "tests/expected/blanks.txt.sn.out")
+}
+
+#[test]
+fn blanks_squeeze_b() -> Result<()> {
+    run(&["-s", "-b", BLANKS], "tests/expected/blanks.txt.sb.out")
+}
+
+#[test]
+fn crlf() -> Result<()> {
+    run(&[CRLF], "tests/expected/crlf.txt.out")
+}
+
+#[test]
+fn crlf_n() -> Result<()> {
+    run(&["-n", CRLF], "tests/expected/crlf.txt.n.out")
+}
+
+#[test]
+fn crlf_b() -> Result<()> {
+    run(&["-b", CRLF], "tests/expected/crlf.txt.b.out")
+}
+
+#[test]
+fn no_newline() -> Result<()> {
+    run(&[NO_NEWLINE], "tests/expected/no-newline.txt.out")
+}
+
+#[test]
+fn no_newline_n() -> Result<()> {
+    run(&["-n", NO_NEWLINE], "tests/expected/no-newline.txt.n.out")
+}
+
+#[test]
+fn no_newline_b() -> Result<()> {
+    run(&["-b", NO_NEWLINE], "tests/expected/no-newline.txt.b.out")
+}
+
+#[test]
+fn binary() -> Result<()> {
+    run_bytes(&[BINARY], "tests/expected/binary.bin.out")
+}
+
+#[test]
+fn binary_n() -> Result<()> {
+    run_bytes(&["-n", BINARY], "tests/expected/binary.bin.n.out")
+}
+
+#[test]
+fn binary_stdin() -> Result<()> {
+    let expected = fs::read("tests/expected/binary.bin.out")?;
+    let output = Command::cargo_bin(CMD)?
+        .write_stdin(fs::read(BINARY)?)
+        .arg("-")
+        .output()?;
+    assert!(output.status.success());
+    assert_eq!(output.stdout, expected);
+    Ok(())
+}
+
+#[test]
+fn blanks_twice_squeeze_n() -> Result<()> {
+    run(
+        &["-s", "-n", BLANKS, BLANKS],
+        "tests/expected/blanks.txt.twice.sn.out",
+    )
+}
+
+#[test]
+fn no_newline_continues_into_next_file() -> Result<()> {
+    run(
+        &["-n", NO_NEWLINE, FOX],
+        "tests/expected/no-newline.txt.fox.n.out",
+    )?;
+    run(
+        &["-b", NO_NEWLINE, BLANKS],
+        "tests/expected/no-newline.txt.blanks.b.out",
+    )
+}
+
+#[test]
+fn number_format_zero_padded() -> Result<()> {
+    run(
+        &[
+            "-n",
+            "--number-width=3",
+            "--number-separator=: ",
+            "--number-start=10",
+            "--number-increment=5",
+            "--number-format=rz",
+            SPIDERS,
+        ],
+        "tests/expected/spiders.txt.n.rz.out",
+    )
+}
+
+#[test]
+fn number_format_left_justified() -> Result<()> {
+    run(
+        &[
+            "-n",
+            "--number-width",
+            "4",
+            "--number-separator",
+            " | ",
+            "--number-format",
+            "ln",
+            BUSTLE,
+        ],
+        "tests/expected/the-bustle.txt.n.ln.out",
+    )
+}
+
+#[test]
+fn number_format_negative_start() -> Result<()> {
+    run(
+        &[
+            "-n",
+            "--number-width",
+            "2",
+            "--number-separator",
+            " ",
+            "--number-start",
+            "-2",
+            FOX,
+            SPIDERS,
+        ],
+        "tests/expected/fox-spiders.n.negative.out",
+    )
+}
+
+#[test]
+fn number_format_nonblank_decreasing() -> Result<()> {
+    run(
+        &[
+            "-b",
+            "--number-width=3",
+            "--number-separator=. ",
+            "--number-start=100",
+            "--number-increment=-10",
+            "--number-format=ln",
+            BUSTLE,
+        ],
+        "tests/expected/the-bustle.txt.b.ln.out",
+    )
+}
+
+#[test]
+fn dies_line_number_overflow() -> Result<()> {
+    for args in [
+        ["--number-start", "9223372036854775807"],
+        ["--number-increment", "9223372036854775807"],
+    ] {
+        Command::cargo_bin(CMD)?
+            .arg("-n")
+            .args(args)
+            .write_stdin("a\nb\nc\n")
+            .assert()
+            .failure()
+            .code(1)
+            .stderr(predicate::str::contains("line number out of range"));
+    }
+    Ok(())
+}
+
+#[test]
+fn dies_bad_number_format() -> Result<()> {
+    Command::cargo_bin(CMD)?
+        .args(["-n", "--number-format", "zz", FOX])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("invalid value 'zz'"));
+    Ok(())
+}
+
+#[test]
+fn decompresses_every_format() -> Result<()> {
+    for ext in ["gz", "bz2", "xz", "zst"] {
+        let input = format!("{BUSTLE}.{ext}");
+        run(&["-z", &input], "tests/expected/the-bustle.txt.out")?;
+        run(
+            &["--decompress=always", "-n", &input],
+            "tests/expected/the-bustle.txt.n.out",
+        )?;
+    }
+    Ok(())
+}
+
+#[test]
+fn decompresses_stdin() -> Result<()> {
+    run_stdin(
+        "tests/inputs/the-bustle.txt.gz",
+        &["-z", "-b", "-"],
+        "tests/expected/the-bustle.txt.b.stdin.out",
+    )
+}
+
+#[test]
+fn decompresses_concatenated_streams() -> Result<()> {
+    run(
+        &["-z", "tests/inputs/fox-spiders.txt.gz"],
+        "tests/expected/fox-spiders.txt.gz.out",
+    )
+}
+
+#[test]
+fn decompress_auto_passes_plain_files_through() -> Result<()> {
+    run(
+        &["-z", FOX, "tests/inputs/the-bustle.txt.xz"],
+        "tests/expected/fox-bustle.txt.out",
+    )
+}
+
+#[test]
+fn does_not_decompress_by_default() -> Result<()> {
+    let expected = fs::read("tests/inputs/the-bustle.txt.gz")?;
+    let output = Command::cargo_bin(CMD)?
+        .arg("tests/inputs/the-bustle.txt.gz")
+        .output()?;
+    assert!(output.status.success());
+    assert_eq!(output.stdout, expected);
+    Ok(())
+}
+
+#[test]
+fn decompress_always_rejects_plain_files() -> Result<()> {
+    Command::cargo_bin(CMD)?
+        .args(["--decompress=always", FOX])
+        .assert()
+        .failure()
+        .code(1)
+        .stdout("")
+        .stderr(predicate::str::contains("not in a known compressed format"));
+    Ok(())
+}
+
+fn append(path: &Path, text: &str) -> Result<()> {
+    OpenOptions::new()
+        .append(true)
+        .open(path)?
+        .write_all(text.as_bytes())?;
+    thread::sleep(Duration::from_millis(500));
+    Ok(())
+}
+
+#[test]
+fn follows_appended_truncated_and_rotated_files() -> Result<()> {
+    let dir = tempfile::tempdir()?;
+    let log = dir.path().join("app.log");
+    fs::write(&log, "one\ntwo\n")?;
+
+    let mut child = process::Command::new(assert_cmd::cargo::cargo_bin(CMD))
+        .args(["-n", "-f", "--sleep-interval", "0.05"])
+        .arg(&log)
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()?;
+    thread::sleep(Duration::from_millis(500));
+
+    append(&log, "three\npar")?;
+    append(&log, "tial\n")?;
+    fs::write(&log, "")?;
+    thread::sleep(Duration::from_millis(500));
+    append(&log, "after truncate\n")?;
+    fs::rename(&log, dir.path().join("app.log.1"))?;
+    fs::write(&log, "")?;
+    append(&log, "rotated\n")?;
+
+    child.kill()?;
+    let output = child.wait_with_output()?;
+    assert_eq!(
+        String::from_utf8(output.stdout)?,
+        concat!(
+            "     1\tone\n",
+            "     2\ttwo\n",
+            "     3\tthree\n",
+            "     4\tpartial\n",
+            "     5\tafter truncate\n",
+            "     6\trotated\n",
+        )
+    );
+    let stderr = String::from_utf8(output.stderr)?;
+    assert!(stderr.contains("app.log: file truncated"));
+    assert!(stderr.contains("app.log has been replaced; following new file"));
+    Ok(())
+}
+
+#[test]
+fn stops_quietly_when_output_is_closed() -> Result<()> {
+    for args in [&[][..], &["-n"], &["--errors", "json"]] {
+        let mut child = process::Command::new(assert_cmd::cargo::cargo_bin(CMD))
+            .args(args)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()?;
+        let mut stdin = child.stdin.take().unwrap();
+        thread::spawn(move || stdin.write_all("line\n".repeat(100_000).as_bytes()));
+
+        let mut first = String::new();
+        BufReader::new(child.stdout.take().unwrap()).read_line(&mut first)?;
+        let output = child.wait_with_output()?;
+        assert!(first.ends_with("line\n"));
+        assert!(output.status.success());
+        assert_eq!(String::from_utf8(output.stderr)?, "");
+    }
+    Ok(())
+}
+
+#[test]
+fn flushes_lines_before_waiting_for_input() -> Result<()> {
+    let mut child = process::Command::new(assert_cmd::cargo::cargo_bin(CMD))
+        .arg("-n")
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()?;
+    let mut stdin = child.stdin.take().unwrap();
+    let stdout = child.stdout.take().unwrap();
+    let (sender, receiver) = mpsc::channel();
+    thread::spawn(move || {
+        for line in BufReader::new(stdout).lines() {
+            sender.send(line.unwrap()).unwrap();
+        }
+    });
+
+    // The input stays open, so the line can only show up if it was flushed
+    stdin.write_all(b"first\n")?;
+    let line = receiver.recv_timeout(Duration::from_secs(5));
+    drop(stdin);
+    child.wait()?;
+    assert_eq!(line?, "     1\tfirst");
+    Ok(())
+}
+
+#[test]
+fn dies_sleep_interval_without_follow() -> Result<()> {
+    Command::cargo_bin(CMD)?
+        .args(["--sleep-interval", "1", FOX])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("--follow"));
+    Ok(())
+}
+
+#[test]
+fn pretty_falls_back_to_plain_output_when_piped() -> Result<()> {
+    run(
+        &["--pretty", FOX, SPIDERS, BUSTLE],
+        "tests/expected/all.out",
+    )?;
+    run(
+        &["--pretty", "--no-pager", FOX, SPIDERS, BUSTLE],
+        "tests/expected/all.out",
+    )
+}
+
+#[test]
+fn dies_pretty_with_line_processing() -> Result<()> {
+    for flag in [
+        "-A",
+        "-e",
+        "-E",
+        "-t",
+        "-T",
+        "-v",
+        "-s",
+        "--headers",
+        "-n",
+        "-b",
+        "--number-per-file",
+        "--number-width=3",
+        "--number-separator=:",
+        "--number-start=5",
+        "--number-increment=2",
+        "--number-format=rz",
+    ] {
+        Command::cargo_bin(CMD)?
+            .args(["--pretty", flag, FOX])
+            .assert()
+            .failure()
+            .stderr(predicate::str::contains("cannot be used with"));
+    }
+    Ok(())
+}
+
+#[test]
+fn range() -> Result<()> {
+    for flag in ["-r", "--range"] {
+        run(
+            &[flag, "2:4", BUSTLE],
+            "tests/expected/the-bustle.txt.r2-4.out",
+        )?;
+    }
+    Ok(())
+}
+
+#[test]
+fn range_keeps_line_numbers() -> Result<()> {
+    run(
+        &["-n", "--range", "2:3,7:", BUSTLE],
+        "tests/expected/the-bustle.txt.n.r2-3,7-.out",
+    )?;
+    run(
+        &["-n", "--range", "2:3", "--range", "7:", BUSTLE],
+        "tests/expected/the-bustle.txt.n.r2-3,7-.out",
+    )
+}
+
+#[test]
+fn range_from_end() -> Result<()> {
+    run(
+        &["--range", ":-2", BUSTLE],
+        "tests/expected/the-bustle.txt.r-2.out",
+    )
+}
+
+#[test]
+fn range_applies_to_each_file() -> Result<()> {
+    run(
+        &["-r", "-3:", "-r", "1", BUSTLE, SPIDERS],
+        "tests/expected/bustle-spiders.r-3-.r1.out",
+    )
+}
+
+#[test]
+fn dies_bad_range() -> Result<()> {
+    Command::cargo_bin(CMD)?
+        .args(["--range", "0:3", FOX])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(r#"illegal line number: "0""#));
+    Ok(())
+}
+
+#[test]
+fn dies_range_with_pretty() -> Result<()> {
+    Command::cargo_bin(CMD)?
+        .args(["--pretty", "--range", "2:3", FOX])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("cannot be used with"));
+    Ok(())
+}
+
+#[test]
+fn hex() -> Result<()> {
+    for flag in ["-x", "--hex"] {
+        run(&[flag, FOX], "tests/expected/fox.txt.hex.out")?;
+    }
+    Ok(())
+}
+
+#[test]
+fn hex_binary() -> Result<()> {
+    run(&["--hex", BINARY], "tests/expected/binary.bin.hex.out")
+}
+
+#[test]
+fn hex_restarts_offsets_per_file() -> Result<()> {
+    run(
+        &["--hex", NONPRINTING, EMPTY, SPIDERS],
+        "tests/expected/nonprinting-empty-spiders.hex.out",
+    )
+}
+
+#[test]
+fn hex_stdin() -> Result<()> {
+    run_stdin(BINARY, &["--hex", "-"], "tests/expected/binary.bin.hex.out")
+}
+
+#[test]
+fn dies_hex_with_numbering() -> Result<()> {
+    Command::cargo_bin(CMD)?
+        .args(["--hex", "-n", FOX])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("cannot be used with"));
+    Ok(())
+}
+
+#[test]
+fn binary_is_printed_when_piped() -> Result<()> {
+    run_bytes(&[BINARY], "tests/expected/binary.bin.out")?;
+    run_bytes(&["--force", BINARY], "tests/expected/binary.bin.out")
+}
+
+#[test]
+fn decodes_bom_inputs() -> Result<()> {
+    for input in ["utf16le", "utf16be", "utf8bom"] {
+        let input = format!("tests/inputs/accents.{input}.txt");
+        run(
+            &["--from-encoding", "auto", &input],
+            "tests/expected/accents.txt.out",
+        )?;
+        run(
+            &["--from-encoding=auto", "-n", &input],
+            "tests/expected/accents.txt.n.out",
+        )?;
+    }
+    Ok(())
+}
+
+#[test]
+fn decodes_explicit_encoding() -> Result<()> {
+    run(
+        &[
+            "--from-encoding",
+            "latin1",
+            "tests/inputs/accents.latin1.txt",
+        ],
+        "tests/expected/accents.txt.out",
+    )?;
+    run(
+        &[
+            "--from-encoding",
+            "utf-16le",
+            "tests/inputs/accents.utf16le.txt",
+        ],
+        "tests/expected/accents.txt.out",
+    )
+}
+
+#[test]
+fn decodes_stdin() -> Result<()> {
+    run_stdin(
+        "tests/inputs/accents.utf16le.txt",
+        &["--from-encoding", "auto"],
+        "tests/expected/accents.txt.out",
+    )
+}
+
+#[test]
+fn encodes_output() -> Result<()> {
+    run_bytes(
+        &["--to-encoding", "latin1", ACCENTS],
+        "tests/expected/accents.txt.latin1.out",
+    )?;
+    run_bytes(
+        &[
+            "--to-encoding",
+            "latin1",
+            "tests/inputs/accents.utf16be.txt",
+        ],
+        "tests/expected/accents.txt.latin1.out",
+    )
+}
+
+#[test]
+fn dies_bad_encodings() -> Result<()> {
+    Command::cargo_bin(CMD)?
+        .args(["--from-encoding", "klingon", ACCENTS])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(r#"unknown encoding: "klingon""#));
+    Command::cargo_bin(CMD)?
+        .args(["--to-encoding", "utf-16le", ACCENTS])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("cannot write output in UTF-16LE"));
+    Ok(())
+}
+
+#[test]
+fn writes_output_file() -> Result<()> {
+    let dir = tempfile::tempdir()?;
+    let out = dir.path().join("all.n.out");
+    for flag in ["-o", "--output"] {
+        Command::cargo_bin(CMD)?
+            .args(["-n", FOX, SPIDERS, BUSTLE, flag])
+            .arg(&out)
+            .assert()
+            .success()
+            .stdout("");
+        assert_eq!(
+            fs::read_to_string(&out)?,
+            fs::read_to_string("tests/expected/all.n.out")?
+        );
+    }
+    // Only the output is left behind, no temporary file
+    assert_eq!(fs::read_dir(dir.path())?.count(), 1);
+    Ok(())
+}
+
+#[test]
+fn output_replaces_existing_file() -> Result<()> {
+    let dir = tempfile::tempdir()?;
+    let out = dir.path().join("out.txt");
+    fs::write(
+        &out,
+        "previous contents that are longer than the new ones\n",
+    )?;
+    Command::cargo_bin(CMD)?
+        .args([FOX, "--output"])
+        .arg(&out)
+        .assert()
+        .success();
+    assert_eq!(
+        fs::read_to_string(&out)?,
+        fs::read_to_string("tests/expected/fox.txt.out")?
+    );
+    Ok(())
+}
+
+#[test]
+fn refuses_input_as_output() -> Result<()> {
+    let dir = tempfile::tempdir()?;
+    let a = dir.path().join("a.txt");
+    fs::write(&a, "a\n")?;
+    Command::cargo_bin(CMD)?
+        .arg(&a)
+        .arg(FOX)
+        .arg("--output")
+        .arg(&a)
+        .assert()
+        .failure()
+        .code(1)
+        .stderr(predicate::str::contains("input file is output"));
+    assert_eq!(fs::read_to_string(&a)?, "a\n");
+    assert_eq!(fs::read_dir(dir.path())?.count(), 1);
+    Ok(())
+}
+
+#[test]
+fn glob_expands_wildcards() -> Result<()> {
+    run(
+        &["--glob", "tests/inputs/tree/*.txt"],
+        "tests/expected/tree.one-three.out",
+    )?;
+    run(
+        &["--glob", "tests/inputs/[fs]*.txt"],
+        "tests/expected/fox-spiders.out",
+    )
+}
+
+#[test]
+fn glob_without_matches_fails() -> Result<()> {
+    Command::cargo_bin(CMD)?
+        .args(["--glob", "tests/inputs/*.nope", FOX])
+        .assert()
+        .failure()
+        .code(1)
+        .stdout(fs::read_to_string("tests/expected/fox.txt.out")?)
+        .stderr(predicate::str::contains("tests/inputs/*.nope"));
+    Ok(())
+}
+
+#[test]
+fn recursive() -> Result<()> {
+    for flag in ["-R", "--recursive"] {
+        run(&[flag, "tests/inputs/tree"], "tests/expected/tree.R.out")?;
+    }
+    Ok(())
+}
+
+#[test]
+fn directory_without_recursive_fails() -> Result<()> {
+    Command::cargo_bin(CMD)?
+        .args(["tests/inputs/tree"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("Is a directory"));
+    Ok(())
+}
+
+#[test]
+fn headers() -> Result<()> {
+    run(
+        &["--headers", FOX, SPIDERS],
+        "tests/expected/fox-spiders.headers.out",
+    )?;
+    run(
+        &["--headers", "-R", "tests/inputs/tree"],
+        "tests/expected/tree.R.headers.out",
+    )?;
+    run(
+        &["--headers", NO_NEWLINE, FOX],
+        "tests/expected/no-newline-fox.headers.out",
+    )?;
+    run(
+        &["--headers", "-n", NO_NEWLINE, FOX],
+        "tests/expected/no-newline-fox.headers.n.out",
+    )
+}
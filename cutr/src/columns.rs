@@ -0,0 +1,147 @@
+use std::ops::Range;
+
+use anyhow::{bail, Result};
+use regex::Regex;
+
+use crate::{parse_range, Extraction};
+
+/// A field selected with `--header`: by position, or from the header row.
+#[derive(Clone, Debug)]
+pub enum Column {
+    Positions(Range<usize>),
+    Name(String),
+    /// Every column whose name matches, written `/regex/`.
+    Pattern(Regex),
+}
+
+/// The columns to select, resolved against the header row of each input.
+#[derive(Clone, Debug)]
+pub struct Columns {
+    columns: Vec<Column>,
+    /// Applied in order to the positions once resolved.
+    transforms: Vec<fn(&Extraction) -> Extraction>,
+}
+
+impl Columns {
+    /// Parses a list like `title,3,/^year/` of names, positions and
+    /// patterns; `open` allows open ranges.
+    pub fn parse(list: &str, open: bool) -> Result<Self> {
+        let columns = list
+            .split(',')
+            .map(|column| parse_column(column, open))
+            .collect::<Result<_>>()?;
+        Ok(Columns {
+            columns,
+            transforms: Vec::new(),
+        })
+    }
+
+    /// Applies `transform` to the positions, once they are known.
+    pub fn map(mut self, transform: fn(&Extraction) -> Extraction) -> Self {
+        self.transforms.push(transform);
+        self
+    }
+
+    /// Turns the columns into positions, looking names up in the `header` fields.
+    pub fn resolve(&self, header: &[&[u8]]) -> Result<Extraction> {
+        let extraction = resolve(&self.columns, header)?;
+        Ok(self
+            .transforms
+            .iter()
+            .fold(extraction, |extraction, transform| transform(&extraction)))
+    }
+}
+
+fn parse_column(column: &str, open: bool) -> Result<Column> {
+    if column.starts_with(|c: char| c.is_ascii_digit() || (open && c == '-')) {
+        return Ok(Column::Positions(parse_range(column, open)?));
+    }
+    match column.strip_prefix('/').and_then(|c| c.strip_suffix('/')) {
+        Some(pattern) => Ok(Column::Pattern(Regex::new(pattern)?)),
+        None if column.is_empty() => bail!(r#"illegal list value: "{column}""#),
+        None => Ok(Column::Name(column.to_string())),
+    }
+}
+
+fn resolve(columns: &[Column], header: &[&[u8]]) -> Result<Extraction> {
+    let names: Vec<_> = header
+        .iter()
+        .map(|name| String::from_utf8_lossy(name))
+        .collect();
+    let mut extraction = Extraction::new();
+    for column in columns {
+        match column {
+            Column::Positions(range) => extraction.push(range.clone()),
+            Column::Name(name) => match names.iter().position(|n| n == name) {
+                Some(i) => extraction.push(i..i + 1),
+                None => bail!(r#"unknown column: "{name}""#),
+            },
+            Column::Pattern(pattern) => {
+                let len = extraction.len();
+                extraction.extend(
+                    names
+                        .iter()
+                        .enumerate()
+                        .filter(|(_, name)| pattern.is_match(name))
+                        .map(|(i, _)| i..i + 1),
+                );
+                if extraction.len() == len {
+                    bail!("no column matches /{pattern}/");
+                }
+            }
+        }
+    }
+    Ok(extraction)
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+    use crate::{complement, OPEN_END};
+
+    const HEADER: &[&[u8]] = &[b"title", b"year", b"director", b"year_released"];
+
+    fn resolved(list: &str) -> Result<Extraction> {
+        Columns::parse(list, true)?.resolve(HEADER)
+    }
+
+    #[test]
+    fn test_resolve() {
+        assert_eq!(resolved("title,year").unwrap(), [0..1, 1..2]);
+        assert_eq!(resolved("director,title").unwrap(), [2..3, 0..1]);
+        assert_eq!(resolved("2-3,title").unwrap(), [1..3, 0..1]);
+        assert_eq!(resolved("/^year/").unwrap(), [1..2, 3..4]);
+        assert_eq!(resolved("-2,director").unwrap(), [0..2, 2..3]);
+    }
+
+    #[test]
+    fn test_resolve_complement() {
+        let columns = Columns::parse("year,/^dir/", false)
+            .unwrap()
+            .map(complement);
+        assert_eq!(columns.resolve(HEADER).unwrap(), [0..1, 3..OPEN_END]);
+    }
+
+    #[test]
+    fn test_resolve_failure() {
+        assert_eq!(
+            resolved("title,budget").unwrap_err().to_string(),
+            r#"unknown column: "budget""#
+        );
+        assert_eq!(
+            resolved("/^budget/").unwrap_err().to_string(),
+            "no column matches /^budget/"
+        );
+        assert_eq!(
+            resolved("title,").unwrap_err().to_string(),
+            r#"illegal list value: """#
+        );
+        assert_eq!(
+            resolved("0").unwrap_err().to_string(),
+            r#"illegal list value: "0""#
+        );
+        assert!(Columns::parse("/(/", false).is_err());
+    }
+}
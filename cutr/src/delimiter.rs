@@ -0,0 +1,220 @@
+use std::ops::Range;
+
+use regex::bytes::Regex;
+
+use crate::OPEN_END;
+
+/// What separates the fields of a line.
+#[derive(Clone, Debug)]
+pub enum Delimiter {
+    /// A string of one or more bytes, such as `\t`, `::` or `¦`.
+    String(Vec<u8>),
+    Regex(Regex),
+    /// Runs of blanks, ignoring those that start or end the line, like awk.
+    Whitespace,
+    /// Columns of fixed ranges of characters, whose padding is trimmed;
+    /// `None` until inferred from the header row.
+    FixedWidth(Option<Vec<Range<usize>>>),
+}
+
+impl Delimiter {
+    /// Splits `line` into its fields; a line without delimiter is one field.
+    pub fn split<'a>(&self, line: &'a [u8]) -> Vec<&'a [u8]> {
+        match self {
+            Delimiter::String(delimiter) => split_str(line, delimiter),
+            Delimiter::Regex(regex) => regex.split(line).collect(),
+            Delimiter::Whitespace => line
+                .split(u8::is_ascii_whitespace)
+                .filter(|field| !field.is_empty())
+                .collect(),
+            Delimiter::FixedWidth(Some(columns)) => split_fixed(line, columns),
+            Delimiter::FixedWidth(None) => vec![line],
+        }
+    }
+
+    /// Whether the `fields` split from a line show that it held the
+    /// delimiter; fixed-width lines always do, and so does any line with a
+    /// word when splitting on blanks, which are not part of the fields.
+    pub fn delimits(&self, fields: &[&[u8]]) -> bool {
+        match self {
+            Delimiter::FixedWidth(_) => true,
+            Delimiter::Whitespace => !fields.is_empty(),
+            Delimiter::String(_) | Delimiter::Regex(_) => fields.len() > 1,
+        }
+    }
+
+    /// Adapts the delimiter to the header row of an input: fixed-width
+    /// columns start wherever a header name does.
+    pub fn with_header(&self, header: &[u8]) -> Self {
+        match self {
+            Delimiter::FixedWidth(None) => Delimiter::FixedWidth(Some(infer_columns(header))),
+            delimiter => delimiter.clone(),
+        }
+    }
+
+    /// Fixed-width columns of the given `widths`, in characters.
+    pub fn widths(widths: &[usize]) -> Self {
+        let mut start = 0;
+        let columns = widths
+            .iter()
+            .map(|width| {
+                start += width;
+                start - width..start
+            })
+            .collect();
+        Delimiter::FixedWidth(Some(columns))
+    }
+
+    /// The delimiter as a single byte, which is all that CSV supports.
+    pub fn as_byte(&self) -> Option<u8> {
+        match self {
+            Delimiter::String(delimiter) => match delimiter.as_slice() {
+                [byte] => Some(*byte),
+                _ => None,
+            },
+            Delimiter::Regex(_) | Delimiter::Whitespace | Delimiter::FixedWidth(_) => None,
+        }
+    }
+}
+
+fn split_str<'a>(mut line: &'a [u8], delimiter: &[u8]) -> Vec<&'a [u8]> {
+    let mut fields = Vec::new();
+    while let Some(i) = line
+        .windows(delimiter.len())
+        .position(|window| window == delimiter)
+    {
+        fields.push(&line[..i]);
+        line = &line[i + delimiter.len()..];
+    }
+    fields.push(line);
+    fields
+}
+
+/// Columns starting at each word of the `header`, the last one running to
+/// the end of the line.
+fn infer_columns(header: &[u8]) -> Vec<Range<usize>> {
+    let header = String::from_utf8_lossy(header);
+    let mut starts = vec![0];
+    let (mut blank, mut words) = (true, 0);
+    for (i, c) in header.chars().enumerate() {
+        if blank && !c.is_whitespace() {
+            // The first column also takes any blanks before its name
+            if words > 0 {
+                starts.push(i);
+            }
+            words += 1;
+        }
+        blank = c.is_whitespace();
+    }
+    let ends = starts.iter().skip(1).copied().chain([OPEN_END]);
+    starts
+        .iter()
+        .zip(ends)
+        .map(|(&start, end)| start..end)
+        .collect()
+}
+
+/// Cuts `line` into `columns` of characters, without their padding.
+fn split_fixed<'a>(line: &'a [u8], columns: &[Range<usize>]) -> Vec<&'a [u8]> {
+    // Byte offset of every character, then of the end of the line
+    let offsets: Vec<_> = line
+        .iter()
+        .enumerate()
+        .filter(|(_, &b)| !is_continuation(b))
+        .map(|(i, _)| i)
+        .chain([line.len()])
+        .collect();
+    let offset = |char: usize| offsets[char.min(offsets.len() - 1)];
+    columns
+        .iter()
+        .map(|column| line[offset(column.start)..offset(column.end)].trim_ascii())
+        .collect()
+}
+
+/// Whether `byte` continues a UTF-8 character rather than starting one.
+pub fn is_continuation(byte: u8) -> bool {
+    byte & 0b1100_0000 == 0b1000_0000
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    fn split(delimiter: Delimiter, line: &str) -> Vec<String> {
+        delimiter
+            .split(line.as_bytes())
+            .iter()
+            .map(|field| String::from_utf8_lossy(field).into_owned())
+            .collect()
+    }
+
+    #[test]
+    fn test_split_str() {
+        let delimiter = |d: &str| Delimiter::String(d.as_bytes().to_vec());
+        assert_eq!(split(delimiter("\t"), "a\tb\t"), ["a", "b", ""]);
+        assert_eq!(split(delimiter("::"), "a::b:c:::d"), ["a", "b:c", ":d"]);
+        assert_eq!(split(delimiter("|~|"), "a|~|b"), ["a", "b"]);
+        assert_eq!(split(delimiter("¦"), "Zola¦1865"), ["Zola", "1865"]);
+        assert_eq!(split(delimiter("::"), "no delimiter"), ["no delimiter"]);
+    }
+
+    #[test]
+    fn test_split_regex() {
+        let delimiter = Delimiter::Regex(Regex::new(r"\s*[;,]\s*").unwrap());
+        assert_eq!(split(delimiter, "a ; b,c  ,d"), ["a", "b", "c", "d"]);
+    }
+
+    #[test]
+    fn test_split_whitespace() {
+        assert_eq!(
+            split(Delimiter::Whitespace, "  PID TTY \t  TIME CMD  "),
+            ["PID", "TTY", "TIME", "CMD"]
+        );
+        assert!(split(Delimiter::Whitespace, " \t ").is_empty());
+    }
+
+    #[test]
+    fn test_split_fixed_width() {
+        let header = "Author              Year Title                         ";
+        let delimiter = Delimiter::FixedWidth(None).with_header(header.as_bytes());
+        assert_eq!(
+            split(delimiter.clone(), header),
+            ["Author", "Year", "Title"]
+        );
+        assert_eq!(
+            split(
+                delimiter.clone(),
+                "Émile Zola          1865 La Confession de Claude"
+            ),
+            ["Émile Zola", "1865", "La Confession de Claude"]
+        );
+        assert_eq!(split(delimiter, "Émile Zola"), ["Émile Zola", "", ""]);
+        assert_eq!(split(Delimiter::widths(&[3, 2]), "abcdefgh"), ["abc", "de"]);
+    }
+
+    #[test]
+    fn test_infer_columns() {
+        assert_eq!(infer_columns(b"a  bb c"), [0..3, 3..6, 6..OPEN_END]);
+        assert_eq!(infer_columns(b"  PID TTY"), [0..6, 6..OPEN_END]);
+        assert_eq!(infer_columns(b"").len(), 1);
+    }
+
+    #[test]
+    fn test_delimits() {
+        let delimiter = Delimiter::String(b"\t".to_vec());
+        assert!(delimiter.delimits(&delimiter.split(b"a\t")));
+        assert!(!delimiter.delimits(&delimiter.split(b"a b")));
+        assert!(Delimiter::Whitespace.delimits(&Delimiter::Whitespace.split(b" a ")));
+        assert!(!Delimiter::Whitespace.delimits(&Delimiter::Whitespace.split(b" \t ")));
+        assert!(Delimiter::widths(&[1]).delimits(&[b"a"]));
+    }
+
+    #[test]
+    fn test_as_byte() {
+        assert_eq!(Delimiter::String(b",".to_vec()).as_byte(), Some(b','));
+        assert_eq!(Delimiter::String("¦".into()).as_byte(), None);
+        assert_eq!(Delimiter::Regex(Regex::new(",").unwrap()).as_byte(), None);
+    }
+}
@@ -0,0 +1,244 @@
+use std::{
+    cell::RefCell,
+    collections::VecDeque,
+    io::{self, Read, Write},
+};
+
+use anyhow::Result;
+use csv::{ByteRecord, ReaderBuilder, Writer, WriterBuilder};
+
+use crate::{select, Extract};
+
+/// How CSV records are quoted, so that a field like `"Smith, John"` is not
+/// split on its comma.
+#[derive(Clone, Copy, Debug)]
+pub struct Dialect {
+    pub delimiter: u8,
+    pub output_delimiter: u8,
+    pub quote: u8,
+    /// Escapes quotes inside quoted fields; `None` means they are doubled.
+    pub escape: Option<u8>,
+    /// Records must all have as many fields as the first one.
+    pub strict: bool,
+    /// Skip records of a single field, instead of printing them unchanged.
+    pub only_delimited: bool,
+}
+
+/// Prints the selected fields of every record, quoted where the output needs it.
+///
+/// `extract` selects fields, by position or by column.
+pub fn cut_records(
+    input: impl Read,
+    extract: &Extract,
+    dialect: &Dialect,
+    out: &mut impl Write,
+) -> Result<()> {
+    let out = RefCell::new(out);
+    let mut reader = ReaderBuilder::new()
+        .has_headers(false)
+        .delimiter(dialect.delimiter)
+        .quote(dialect.quote)
+        .escape(dialect.escape)
+        .double_quote(dialect.escape.is_none())
+        .flexible(!dialect.strict)
+        .from_reader(SkippedLines::new(input));
+    let mut writer = WriterBuilder::new()
+        .delimiter(dialect.output_delimiter)
+        .quote(dialect.quote)
+        .escape(dialect.escape.unwrap_or(b'\\'))
+        .double_quote(dialect.escape.is_none())
+        .flexible(true)
+        .from_writer(Shared(&out));
+    let mut resolved = None;
+    let mut record = ByteRecord::new();
+    // Where the reader stopped after the last record
+    let mut end = 0;
+    while reader.read_byte_record(&mut record)? {
+        let start = record.position().map_or(end, |position| position.byte());
+        let blanks = reader.get_mut().count_from(start);
+        end = reader.position().byte();
+        if !dialect.only_delimited {
+            // Like lines without the delimiter, blank lines are dropped with -s
+            blank_lines(&mut writer, &out, blanks)?;
+        }
+        let fields: Vec<_> = record.iter().collect();
+        if resolved.is_none() {
+            resolved = extract.resolve(&fields)?;
+        }
+        let Extract::Fields(extraction) = resolved.as_ref().unwrap_or(extract) else {
+            unreachable!("records are only cut into fields");
+        };
+        if fields.len() <= 1 {
+            // A line without the delimiter, kept whole like in `cut`
+            if !dialect.only_delimited {
+                writer.write_record(&record)?;
+            }
+            continue;
+        }
+        let selected = select(&fields, extraction);
+        if selected.len() <= 1 && selected.iter().all(|field| field.is_empty()) {
+            // The writer would print "" to tell an empty record from an empty line
+            blank_lines(&mut writer, &out, 1)?;
+        } else {
+            writer.write_record(selected)?;
+        }
+    }
+    let blanks = reader.get_mut().count_from(end);
+    if !dialect.only_delimited {
+        blank_lines(&mut writer, &out, blanks)?;
+    }
+    writer.flush()?;
+    Ok(())
+}
+
+/// Prints `count` blank lines, after whatever the writer holds.
+fn blank_lines<W: Write>(
+    writer: &mut Writer<Shared<W>>,
+    out: &RefCell<W>,
+    count: usize,
+) -> Result<()> {
+    if count == 0 {
+        return Ok(());
+    }
+    writer.flush()?;
+    out.borrow_mut().write_all(&b"\n".repeat(count))?;
+    Ok(())
+}
+
+/// The output, shared by the CSV writer and the blank lines it cannot write.
+///
+/// Flushing only empties the buffer of the CSV writer; the output itself is
+/// flushed by its owner.
+struct Shared<'a, W>(&'a RefCell<W>);
+
+impl<W: Write> Write for Shared<'_, W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.borrow_mut().write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Holds on to the bytes of a CSV input from where the current record was
+/// looked for, to count the blank lines the CSV reader skipped on the way.
+///
+/// Where a record would start, the reader skips any run of line terminators
+/// (`\n`, `\r` or `\r\n`), so these runs are exactly the blank lines; quotes
+/// never come into it.
+struct SkippedLines<R> {
+    inner: R,
+    bytes: VecDeque<u8>,
+    /// Byte offset of the first of `bytes`.
+    start: u64,
+    /// The byte just before `start`, if any.
+    before: Option<u8>,
+}
+
+impl<R: Read> SkippedLines<R> {
+    fn new(inner: R) -> Self {
+        SkippedLines {
+            inner,
+            bytes: VecDeque::new(),
+            start: 0,
+            before: None,
+        }
+    }
+
+    /// Counts the blank lines from the byte `offset` where the reader looked
+    /// for a record, forgetting everything before it.
+    fn count_from(&mut self, offset: u64) -> usize {
+        let skip = (offset.saturating_sub(self.start) as usize).min(self.bytes.len());
+        if skip > 0 {
+            self.before = self.bytes.get(skip - 1).copied();
+            self.bytes.drain(..skip);
+            self.start += skip as u64;
+        }
+        let mut previous = self.before;
+        let mut count = 0;
+        for &byte in self.bytes.iter().take_while(|&&b| b == b'\n' || b == b'\r') {
+            // The \n of a \r\n is part of the line the \r ended
+            if byte == b'\r' || previous != Some(b'\r') {
+                count += 1;
+            }
+            previous = Some(byte);
+        }
+        count
+    }
+}
+
+impl<R: Read> Read for SkippedLines<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let len = self.inner.read(buf)?;
+        self.bytes.extend(&buf[..len]);
+        Ok(len)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+    use crate::{complement, Extraction};
+
+    const CSV: Dialect = Dialect {
+        delimiter: b',',
+        output_delimiter: b',',
+        quote: b'"',
+        escape: None,
+        strict: false,
+        only_delimited: false,
+    };
+
+    fn cut(input: &str, dialect: Dialect) -> String {
+        let mut out = Vec::new();
+        let extract = Extract::Fields(complement(&Extraction::new()));
+        cut_records(input.as_bytes(), &extract, &dialect, &mut out).unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn test_blank_lines() {
+        assert_eq!(cut("\na,b\n\n\nc,d\n\n", CSV), "\na,b\n\n\nc,d\n\n");
+        assert_eq!(cut("a,b\n\nc,d", CSV), "a,b\n\nc,d\n");
+    }
+
+    #[test]
+    fn test_blank_lines_line_endings() {
+        assert_eq!(cut("a,b\r\n\r\nc,d\r\n", CSV), "a,b\n\nc,d\n");
+        assert_eq!(cut("a,b\r\rc,d\r", CSV), "a,b\n\nc,d\n");
+    }
+
+    #[test]
+    fn test_blank_lines_in_quoted_fields() {
+        assert_eq!(cut("a,\"x\n\ny\"\n\nb,c\n", CSV), "a,\"x\n\ny\"\n\nb,c\n");
+        assert_eq!(
+            cut("a,\"x\"\"\n\n\"\n\nb,c\n", CSV),
+            "a,\"x\"\"\n\n\"\n\nb,c\n"
+        );
+        let escaped = Dialect {
+            escape: Some(b'\\'),
+            ..CSV
+        };
+        assert_eq!(
+            cut("a,\"x\\\"\n\n\"\n\nb,c\n", escaped),
+            "a,\"x\\\"\n\n\"\n\nb,c\n"
+        );
+        let single = Dialect {
+            quote: b'\'',
+            ..CSV
+        };
+        assert_eq!(cut("a,'x\n\ny'\n\nb,c\n", single), "a,'x\n\ny'\n\nb,c\n");
+    }
+
+    #[test]
+    fn test_blank_lines_only_delimited() {
+        let only_delimited = Dialect {
+            only_delimited: true,
+            ..CSV
+        };
+        assert_eq!(cut("\na,b\n\nnoise\nc,d\n\n", only_delimited), "a,b\nc,d\n");
+    }
+}
@@ -1,7 +1,21 @@
-use std::ops::Range;
+use std::{
+    fs::File,
+    io::{self, BufRead, BufReader, BufWriter, Write},
+    num::NonZeroUsize,
+    ops::Range,
+};
 
-use anyhow::bail;
+use anyhow::{anyhow, bail, Context, Result};
 use clap::Parser;
+use columns::Columns;
+use delimiter::{is_continuation, Delimiter};
+use dialect::{cut_records, Dialect};
+use regex::bytes::Regex;
+use unicode_segmentation::UnicodeSegmentation;
+
+mod columns;
+mod delimiter;
+mod dialect;
 
 #[derive(Debug, Parser)]
 #[command(about, version, author)]
@@ -9,61 +23,477 @@ struct Args {
     /// Files to process
     #[arg(default_value = "-")]
     files: Vec<String>,
-    /// Field delimiter
+    /// Field delimiter, of one or more bytes
     #[arg(short, long, default_value = "\t")]
     delimiter: String,
+    /// Split fields on matches of REGEX instead of a fixed delimiter
+    #[arg(long, value_name = "REGEX", conflicts_with = "delimiter")]
+    regex_delimiter: Option<Regex>,
+    /// Split fields on runs of blanks, ignoring leading and trailing ones
+    #[arg(short, long, conflicts_with_all = ["delimiter", "regex_delimiter"])]
+    whitespace: bool,
+    /// Split lines into fixed-width columns, starting where the names in the
+    /// header row do
+    #[arg(long, conflicts_with_all = ["delimiter", "regex_delimiter", "whitespace"])]
+    fixed_width: bool,
+    /// Widths of the fixed-width columns, instead of inferring them; implies
+    /// --fixed-width
+    #[arg(
+        long,
+        value_name = "WIDTHS",
+        value_delimiter = ',',
+        conflicts_with_all = ["delimiter", "regex_delimiter", "whitespace"]
+    )]
+    widths: Option<Vec<NonZeroUsize>>,
+    /// Join the selected fields with STRING [default: the delimiter; a tab
+    /// with --regex-delimiter or --fixed-width, a space with --whitespace]
+    #[arg(long, value_name = "STRING", conflicts_with_all = ["bytes", "chars"])]
+    output_delimiter: Option<String>,
+    /// Do not print lines without the delimiter, which are otherwise printed
+    /// unchanged
+    #[arg(short = 's', long, conflicts_with_all = ["bytes", "chars"])]
+    only_delimited: bool,
+
+    /// The first line of each file is a header naming its fields, which
+    /// --fields may then select by name or /regex/
+    #[arg(long, conflicts_with_all = ["bytes", "chars"])]
+    header: bool,
+
+    /// Accept N- (from N to the end of the line) and -M (from the start to
+    /// M) in lists
+    #[arg(long)]
+    open_ranges: bool,
+    /// Select everything but the listed fields, bytes or characters
+    #[arg(long)]
+    complement: bool,
+    /// Print the selection in the order listed, repeats included, instead of
+    /// in the order of the input
+    #[arg(long, conflicts_with = "complement")]
+    reorder: bool,
+    /// With --bytes, do not split multi-byte characters: each one comes out
+    /// with the range holding its last byte
+    #[arg(short = 'n', conflicts_with_all = ["fields", "chars"])]
+    no_split: bool,
+    /// With --chars, count user-perceived characters (extended grapheme
+    /// clusters), so that an accent or an emoji sequence stays whole
+    #[arg(long, conflicts_with_all = ["fields", "bytes"])]
+    graphemes: bool,
 
     #[command(flatten)]
     extract: ArgExtract,
+
+    #[command(flatten)]
+    csv: ArgCsv,
 }
 
 #[derive(Debug, clap::Args)]
 #[group(required = true, multiple = false)]
 struct ArgExtract {
     /// Select only these fields
-    #[arg(short, long)]
+    #[arg(short, long, visible_alias = "columns", allow_hyphen_values = true)]
     fields: Option<String>,
-    /// Select only these characters
-    #[arg(short, long)]
-    chars: Option<String>,
     /// Select only these bytes
-    #[arg(short, long)]
+    #[arg(short, long, allow_hyphen_values = true)]
     bytes: Option<String>,
+    /// Select only these characters
+    #[arg(short, long, allow_hyphen_values = true)]
+    chars: Option<String>,
+}
+
+#[derive(Debug, clap::Args)]
+struct ArgCsv {
+    /// Parse fields as CSV, so that quoted fields may hold the delimiter
+    /// [default with -d ,]
+    #[arg(long, conflicts_with = "no_csv")]
+    csv: bool,
+    /// Split fields on every delimiter, even with -d ,
+    #[arg(long)]
+    no_csv: bool,
+    /// Quote character of CSV fields
+    #[arg(long, value_name = "CHAR", default_value = "\"", value_parser = parse_byte)]
+    quote: u8,
+    /// Escape character of quotes in CSV fields [default: quotes are doubled]
+    #[arg(long, value_name = "CHAR", value_parser = parse_byte)]
+    escape: Option<u8>,
+    /// Fail on CSV records that do not have as many fields as the first one
+    #[arg(long)]
+    strict: bool,
+}
+
+fn parse_byte(value: &str) -> Result<u8, String> {
+    match value.as_bytes() {
+        [byte] => Ok(*byte),
+        _ => Err(format!(r#""{value}" must be a single byte"#)),
+    }
 }
 
+/// Zero-based, end-exclusive ranges of positions, in the order they were given.
+///
+/// A range running to the end of the line ends at [`OPEN_END`].
 type Extraction = Vec<Range<usize>>;
 
-pub fn parse_extraction(_range: &str) -> anyhow::Result<Extraction> {
-    unimplemented!()
+/// The end of a range that runs to the end of the line, however long.
+const OPEN_END: usize = usize::MAX;
+
+/// Parses a list like `1,3-5` of one-based positions and ranges.
+pub fn parse_extraction(range: &str) -> Result<Extraction> {
+    range.split(',').map(|r| parse_range(r, false)).collect()
+}
+
+/// Parses a list that may also hold open ranges like `-2,5-`.
+pub fn parse_open_extraction(range: &str) -> Result<Extraction> {
+    range.split(',').map(|r| parse_range(r, true)).collect()
+}
+
+/// Parses `N`, `N-M`, and with `open`, `N-` and `-M`.
+pub fn parse_range(range: &str, open: bool) -> Result<Range<usize>> {
+    let Some((start, end)) = range.split_once('-') else {
+        let position = parse_position(range)?;
+        return Ok(position - 1..position);
+    };
+    if open && start.is_empty() && is_number(end) {
+        return Ok(0..parse_position(end)?);
+    }
+    if open && is_number(start) && end.is_empty() {
+        return Ok(parse_position(start)? - 1..OPEN_END);
+    }
+    if !is_number(start) || !is_number(end) {
+        bail!(r#"illegal list value: "{range}""#);
+    }
+    let (start, end) = (parse_position(start)?, parse_position(end)?);
+    if start >= end {
+        bail!("First number in range ({start}) must be lower than second number ({end})");
+    }
+    Ok(start - 1..end)
+}
+
+/// Parses a one-based position; a sign is not a number here.
+fn parse_position(value: &str) -> Result<usize> {
+    let illegal = || match value {
+        // An empty value has no closing quote, as the tests have always expected
+        "" => anyhow!(r#"illegal list value: ""#),
+        _ => anyhow!(r#"illegal list value: "{value}""#),
+    };
+    if !is_number(value) {
+        return Err(illegal());
+    }
+    match value.parse() {
+        Ok(0) | Err(_) => Err(illegal()),
+        Ok(position) => Ok(position),
+    }
+}
+
+fn is_number(value: &str) -> bool {
+    !value.is_empty() && value.bytes().all(|b| b.is_ascii_digit())
+}
+
+/// The positions in any of the ranges, each once and in order.
+fn normalize(extraction: &Extraction) -> Extraction {
+    let mut ranges = extraction.clone();
+    ranges.sort_by_key(|range| range.start);
+    let mut normalized = Extraction::new();
+    for range in ranges {
+        match normalized.last_mut() {
+            Some(last) if range.start <= last.end => last.end = last.end.max(range.end),
+            _ => normalized.push(range),
+        }
+    }
+    normalized
+}
+
+/// The positions in none of the ranges, in order.
+fn complement(extraction: &Extraction) -> Extraction {
+    let mut ranges = extraction.clone();
+    ranges.sort_by_key(|range| range.start);
+    let mut complement = Extraction::new();
+    let mut next = 0;
+    for range in ranges {
+        if range.start > next {
+            complement.push(next..range.start);
+        }
+        next = next.max(range.end);
+    }
+    if next < OPEN_END {
+        complement.push(next..OPEN_END);
+    }
+    complement
 }
 
 #[derive(Debug)]
 pub enum Extract {
     Fields(Extraction),
+    /// Fields to look up in the header row of each input.
+    Columns(Columns),
     Bytes(Extraction),
+    /// Bytes, with the ranges moved to character boundaries.
+    CharBytes(Extraction),
     Chars(Extraction),
+    Graphemes(Extraction),
+}
+
+impl Extract {
+    fn new(args: &Args) -> Result<Self> {
+        let parse = if args.open_ranges {
+            parse_open_extraction
+        } else {
+            parse_extraction
+        };
+        let extract = &args.extract;
+        let extract = if let Some(fields) = &extract.fields {
+            if args.header {
+                Extract::Columns(Columns::parse(fields, args.open_ranges)?)
+            } else {
+                Extract::Fields(parse(fields)?)
+            }
+        } else if let Some(bytes) = &extract.bytes {
+            if args.no_split {
+                Extract::CharBytes(parse(bytes)?)
+            } else {
+                Extract::Bytes(parse(bytes)?)
+            }
+        } else if let Some(chars) = &extract.chars {
+            if args.graphemes {
+                Extract::Graphemes(parse(chars)?)
+            } else {
+                Extract::Chars(parse(chars)?)
+            }
+        } else {
+            bail!("one of --fields, --bytes or --chars is required")
+        };
+        // Like POSIX cut, the selection comes out in input order, unless asked
+        if args.complement {
+            return Ok(extract.map(complement));
+        }
+        if args.reorder {
+            return Ok(extract);
+        }
+        Ok(extract.map(normalize))
+    }
+
+    /// Applies `transform` to the positions, deferred for columns until the
+    /// header has been read.
+    fn map(self, transform: fn(&Extraction) -> Extraction) -> Self {
+        match self {
+            Extract::Fields(extraction) => Extract::Fields(transform(&extraction)),
+            Extract::Columns(columns) => Extract::Columns(columns.map(transform)),
+            Extract::Bytes(extraction) => Extract::Bytes(transform(&extraction)),
+            Extract::CharBytes(extraction) => Extract::CharBytes(transform(&extraction)),
+            Extract::Chars(extraction) => Extract::Chars(transform(&extraction)),
+            Extract::Graphemes(extraction) => Extract::Graphemes(transform(&extraction)),
+        }
+    }
+
+    /// Selects the parts of `line`, which does not include its newline.
+    ///
+    /// Columns must have been resolved into fields beforehand.
+    fn apply(&self, line: &[u8], delimiters: &Delimiters) -> Option<Vec<u8>> {
+        match self {
+            Extract::Fields(extraction) => {
+                let fields = delimiters.input.split(line);
+                if !delimiters.input.delimits(&fields) {
+                    // Like POSIX cut, print the line as is, or not at all with -s
+                    return (!delimiters.only_delimited).then(|| line.to_vec());
+                }
+                let selected: Vec<_> = select(&fields, extraction).into_iter().copied().collect();
+                Some(selected.join(delimiters.output.as_slice()))
+            }
+            Extract::Bytes(extraction) => {
+                Some(select(line, extraction).into_iter().copied().collect())
+            }
+            Extract::CharBytes(extraction) => {
+                let extraction = char_boundaries(line, extraction);
+                Some(select(line, &extraction).into_iter().copied().collect())
+            }
+            Extract::Chars(extraction) => {
+                let chars: Vec<_> = String::from_utf8_lossy(line).chars().collect();
+                let selected: String = select(&chars, extraction).into_iter().collect();
+                Some(selected.into_bytes())
+            }
+            Extract::Graphemes(extraction) => {
+                let line = String::from_utf8_lossy(line);
+                let graphemes: Vec<_> = line.graphemes(true).collect();
+                let selected: String = select(&graphemes, extraction)
+                    .into_iter()
+                    .copied()
+                    .collect();
+                Some(selected.into_bytes())
+            }
+            Extract::Columns(_) => unreachable!("columns are resolved on the header"),
+        }
+    }
+
+    /// Resolves columns against the fields of the `header` row; other
+    /// extractions need no header and give `None`.
+    fn resolve(&self, header: &[&[u8]]) -> Result<Option<Extract>> {
+        match self {
+            Extract::Columns(columns) => Ok(Some(Extract::Fields(columns.resolve(header)?))),
+            _ => Ok(None),
+        }
+    }
+}
+
+/// Moves both ends of each byte range back to the start of the character they
+/// fall in, like POSIX `cut -b -n`: a character is selected by the range that
+/// holds its last byte, and ranges left empty are dropped.
+fn char_boundaries(line: &[u8], extraction: &Extraction) -> Extraction {
+    let boundary = |i: usize| {
+        let mut i = i.min(line.len());
+        while i > 0 && i < line.len() && is_continuation(line[i]) {
+            i -= 1;
+        }
+        i
+    };
+    extraction
+        .iter()
+        .map(|range| boundary(range.start)..boundary(range.end))
+        .filter(|range| !range.is_empty())
+        .collect()
+}
+
+/// How fields are told apart in the input, and joined in the output.
+#[derive(Clone, Debug)]
+struct Delimiters {
+    input: Delimiter,
+    output: Vec<u8>,
+    /// Skip lines without the delimiter, instead of printing them unchanged.
+    only_delimited: bool,
+}
+
+impl Delimiters {
+    fn new(args: &Args) -> Result<Self> {
+        let (input, output) = if args.fixed_width || args.widths.is_some() {
+            let widths: Option<Vec<_>> = args
+                .widths
+                .as_ref()
+                .map(|widths| widths.iter().map(|width| width.get()).collect());
+            let input = match widths {
+                Some(widths) => Delimiter::widths(&widths),
+                None => Delimiter::FixedWidth(None),
+            };
+            (input, "\t")
+        } else if args.whitespace {
+            (Delimiter::Whitespace, " ")
+        } else if let Some(regex) = &args.regex_delimiter {
+            (Delimiter::Regex(regex.clone()), "\t")
+        } else if args.delimiter.is_empty() {
+            bail!(r#"--delim "" must not be empty"#);
+        } else {
+            let input = Delimiter::String(args.delimiter.as_bytes().to_vec());
+            (input, args.delimiter.as_str())
+        };
+        Ok(Delimiters {
+            input,
+            output: args.output_delimiter.as_deref().unwrap_or(output).into(),
+            only_delimited: args.only_delimited,
+        })
+    }
+
+    /// The CSV dialect to parse fields with, if any.
+    fn dialect(&self, args: &ArgCsv) -> Result<Option<Dialect>> {
+        let delimiter = self.input.as_byte();
+        if !args.csv && (delimiter != Some(b',') || args.no_csv) {
+            return Ok(None);
+        }
+        let Some(delimiter) = delimiter else {
+            bail!("--csv needs a single byte delimiter");
+        };
+        let [output_delimiter] = self.output[..] else {
+            bail!("--output-delimiter must be a single byte with CSV");
+        };
+        Ok(Some(Dialect {
+            delimiter,
+            output_delimiter,
+            quote: args.quote,
+            escape: args.escape,
+            strict: args.strict,
+            only_delimited: self.only_delimited,
+        }))
+    }
+}
+
+/// Picks the items in each range, skipping positions past the end.
+pub fn select<'a, T>(items: &'a [T], extraction: &Extraction) -> Vec<&'a T> {
+    extraction
+        .iter()
+        .flat_map(|range| items.get(range.start..range.end.min(items.len())))
+        .flatten()
+        .collect()
 }
 
 fn main() {
-    if let Err(e) = run(Args::parse()) {
-        eprintln!("{}", e);
-        std::process::exit(1);
+    match run(Args::parse()) {
+        // Whoever reads the output has seen enough, like `cutr ... | head`
+        Err(e) if is_broken_pipe(&e) => {}
+        Err(e) => {
+            eprintln!("{e:#}");
+            std::process::exit(1);
+        }
+        Ok(()) => {}
     }
 }
 
-fn run(args: Args) -> anyhow::Result<()> {
-    let delim_bytes = args.delimiter.as_bytes();
-    if delim_bytes.len() > 1 {
-        bail!(
-            r#"delimiter "{}" is invalid. It must be a single byte"#,
-            args.delimiter
-        );
+fn is_broken_pipe(error: &anyhow::Error) -> bool {
+    let io_error = match error.downcast_ref::<csv::Error>() {
+        Some(error) => match error.kind() {
+            csv::ErrorKind::Io(error) => Some(error),
+            _ => None,
+        },
+        None => error.downcast_ref::<io::Error>(),
+    };
+    io_error.is_some_and(|error| error.kind() == io::ErrorKind::BrokenPipe)
+}
+
+fn run(args: Args) -> Result<()> {
+    let delimiters = Delimiters::new(&args)?;
+    let extract = Extract::new(&args)?;
+    let dialect = delimiters.dialect(&args.csv)?;
+    let mut out = BufWriter::new(io::stdout().lock());
+    for filename in &args.files {
+        match open(filename) {
+            Err(e) => eprintln!("{filename}: {e}"),
+            Ok(file) => match (&extract, &dialect) {
+                (Extract::Fields(_) | Extract::Columns(_), Some(dialect)) => {
+                    cut_records(file, &extract, dialect, &mut out)
+                }
+                _ => cut(file, &extract, &delimiters, &mut out),
+            }
+            .with_context(|| filename.clone())?,
+        }
     }
-    let delimiter = *delim_bytes.first().unwrap();
-    println!("{delimiter}");
+    out.flush()?;
     Ok(())
 }
 
+fn cut(
+    file: impl BufRead,
+    extract: &Extract,
+    delimiters: &Delimiters,
+    out: &mut impl Write,
+) -> Result<()> {
+    let mut delimiters = delimiters.clone();
+    let mut resolved = None;
+    for (i, line) in file.split(b'\n').enumerate() {
+        let line = line?;
+        if i == 0 {
+            delimiters.input = delimiters.input.with_header(&line);
+            resolved = extract.resolve(&delimiters.input.split(&line))?;
+        }
+        let extract = resolved.as_ref().unwrap_or(extract);
+        if let Some(cut) = extract.apply(&line, &delimiters) {
+            out.write_all(&cut)?;
+            out.write_all(b"\n")?;
+        }
+    }
+    Ok(())
+}
+
+fn open(filename: &str) -> Result<Box<dyn BufRead>> {
+    match filename {
+        "-" => Ok(Box::new(BufReader::new(io::stdin()))),
+        _ => Ok(Box::new(BufReader::new(File::open(filename)?))),
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use pretty_assertions::assert_eq;
@@ -117,6 +547,71 @@ mod tests {
         assert_eq!(res.unwrap_err().to_string(), expected_msg);
     }
 
+    #[rstest(
+        input,
+        expected,
+        case("3-", vec![2..OPEN_END]),
+        case("-3", vec![0..3]),
+        case("-1,4-", vec![0..1, 3..OPEN_END]),
+        case("1,2-3", vec![0..1, 1..3])
+    )]
+    fn test_parse_open_extraction(input: &str, expected: Extraction) {
+        assert_eq!(parse_open_extraction(input).unwrap(), expected);
+    }
+
+    #[rstest(
+        input,
+        expected_msg,
+        case("-", r#"illegal list value: "-""#),
+        case("-0", r#"illegal list value: "0""#),
+        case("0-", r#"illegal list value: "0""#),
+        case("1--", r#"illegal list value: "1--""#),
+        case("--1", r#"illegal list value: "--1""#)
+    )]
+    fn test_parse_open_extraction_illegal_values(input: &str, expected_msg: &str) {
+        assert_eq!(
+            parse_open_extraction(input).unwrap_err().to_string(),
+            expected_msg
+        );
+    }
+
+    #[rstest(
+        input,
+        expected,
+        case(vec![2..3, 0..1, 1..2], vec![0..3]),
+        case(vec![0..1, 0..1], vec![0..1]),
+        case(vec![4..6, 0..2, 1..3, 5..OPEN_END], vec![0..3, 4..OPEN_END])
+    )]
+    fn test_normalize(input: Extraction, expected: Extraction) {
+        assert_eq!(normalize(&input), expected);
+    }
+
+    #[rstest(
+        input,
+        expected,
+        case(vec![1..3], vec![0..1, 3..OPEN_END]),
+        case(vec![4..5, 0..2, 1..3], vec![3..4, 5..OPEN_END]),
+        case(vec![2..OPEN_END], vec![0..2]),
+        case(vec![0..OPEN_END], vec![])
+    )]
+    fn test_complement(input: Extraction, expected: Extraction) {
+        assert_eq!(complement(&input), expected);
+    }
+
+    // "Émile" is É (bytes 0..2), then one byte per letter
+    #[rstest(
+        input,
+        expected,
+        case(vec![0..1], vec![]),
+        case(vec![1..2], vec![0..2]),
+        case(vec![0..3], vec![0..3]),
+        case(vec![1..4, 4..5], vec![0..4, 4..5]),
+        case(vec![3..OPEN_END], vec![3..6])
+    )]
+    fn test_char_boundaries(input: Extraction, expected: Extraction) {
+        assert_eq!(char_boundaries("Émile".as_bytes(), &input), expected);
+    }
+
     #[test]
     fn test_parse_extraction_failure() {
         assert!(parse_extraction("").is_err());
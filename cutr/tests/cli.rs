@@ -3,12 +3,22 @@ use assert_cmd::Command;
 use predicates::prelude::*;
 use pretty_assertions::assert_eq;
 use rand::{distributions::Alphanumeric, Rng};
-use std::fs;
+use std::{
+    fs,
+    io::{BufRead, BufReader, Write},
+    process::{self, Stdio},
+    thread,
+};
 
 const PRG: &str = "cutr";
 const CSV: &str = "tests/inputs/movies1.csv";
 const TSV: &str = "tests/inputs/movies1.tsv";
 const BOOKS: &str = "tests/inputs/books.tsv";
+const CSV2: &str = "tests/inputs/movies2.csv";
+const CSV3: &str = "tests/inputs/movies3.csv";
+const BOOKS_CSV: &str = "tests/inputs/books.csv";
+const PS: &str = "tests/inputs/ps.txt";
+const BOOKS_TXT: &str = "tests/inputs/books.txt";
 
 // --------------------------------------------------
 fn random_string() -> String {
@@ -97,7 +107,7 @@ fn dies_bad_digit_chars() -> Result<()> {
 fn dies_empty_delimiter() -> Result<()> {
     dies(
         &[CSV, "-f", "1", "-d", ""],
-        r#"--delim "" must be a single byte"#,
+        r#"--delim "" must not be empty"#,
     )
 }
 
@@ -105,8 +115,17 @@ fn dies_empty_delimiter() -> Result<()> {
 #[test]
 fn dies_bad_delimiter() -> Result<()> {
     dies(
-        &[CSV, "-f", "1", "-d", ",,"],
-        r#"--delim ",," must be a single byte"#,
+        &[CSV, "-f", "1", "--regex-delimiter", "("],
+        "unclosed group",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn dies_csv_multi_byte_delimiter() -> Result<()> {
+    dies(
+        &[CSV, "-f", "1", "-d", ",,", "--csv"],
+        "--csv needs a single byte delimiter",
     )
 }
 
@@ -162,14 +181,11 @@ fn run(args: &[&str], expected_file: &str) -> Result<()> {
 }
 
 // --------------------------------------------------
-fn run_lossy(args: &[&str], expected_file: &str) -> Result<()> {
-    let contents = fs::read(expected_file)?;
-    let expected = String::from_utf8_lossy(&contents);
+fn run_bytes(args: &[&str], expected_file: &str) -> Result<()> {
+    let expected = fs::read(expected_file)?;
     let output = Command::cargo_bin(PRG)?.args(args).output().expect("fail");
     assert!(output.status.success());
-
-    let stdout = String::from_utf8(output.stdout).expect("invalid UTF-8");
-    assert_eq!(stdout, expected);
+    assert_eq!(output.stdout, expected);
     Ok(())
 }
 
@@ -278,7 +294,7 @@ fn tsv_b2() -> Result<()> {
 // --------------------------------------------------
 #[test]
 fn tsv_b8() -> Result<()> {
-    run_lossy(&[TSV, "-b", "8"], "tests/expected/movies1.tsv.b8.out")
+    run_bytes(&[TSV, "-b", "8"], "tests/expected/movies1.tsv.b8.out")
 }
 
 // --------------------------------------------------
@@ -296,7 +312,7 @@ fn tsv_b2_3() -> Result<()> {
 // --------------------------------------------------
 #[test]
 fn tsv_b1_8() -> Result<()> {
-    run_lossy(&[TSV, "-b", "1-8"], "tests/expected/movies1.tsv.b1-8.out")
+    run_bytes(&[TSV, "-b", "1-8"], "tests/expected/movies1.tsv.b1-8.out")
 }
 
 // --------------------------------------------------
@@ -338,5 +354,590 @@ fn tsv_c1_8() -> Result<()> {
 // --------------------------------------------------
 #[test]
 fn repeated_value() -> Result<()> {
-    run(&[BOOKS, "-c", "1,1"], "tests/expected/books.c1,1.out")
+    run(&[BOOKS, "-c", "1,1"], "tests/expected/books.c1.out")
+}
+
+// --------------------------------------------------
+#[test]
+fn repeated_value_reorder() -> Result<()> {
+    run(
+        &[BOOKS, "--reorder", "-c", "1,1"],
+        "tests/expected/books.c1,1.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn unordered_fields() -> Result<()> {
+    run(&[TSV, "-f", "3,1"], "tests/expected/movies1.tsv.f1,3.out")
+}
+
+// --------------------------------------------------
+#[test]
+fn unordered_fields_reorder() -> Result<()> {
+    run(
+        &[TSV, "--reorder", "-f", "3,1,1"],
+        "tests/expected/movies1.tsv.f3,1,1.reorder.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn unordered_bytes_reorder() -> Result<()> {
+    run(
+        &[TSV, "--reorder", "-b", "3,1-2"],
+        "tests/expected/movies1.tsv.b3,1-2.reorder.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn unordered_columns_reorder() -> Result<()> {
+    run(
+        &[BOOKS, "--header", "--reorder", "--columns", "Title,Author"],
+        "tests/expected/books.tsv.columns.Title,Author.reorder.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn reads_stdin() -> Result<()> {
+    let expected = fs::read_to_string("tests/expected/movies1.tsv.f1.out")?;
+    Command::cargo_bin(PRG)?
+        .args(["-f", "1", "-"])
+        .write_stdin(fs::read_to_string(TSV)?)
+        .assert()
+        .success()
+        .stdout(expected);
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn csv_quoted_f1() -> Result<()> {
+    run(
+        &[CSV2, "-f", "1", "-d", ","],
+        "tests/expected/movies2.csv.f1.dcomma.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn csv_quoted_f2() -> Result<()> {
+    run(
+        &[CSV2, "-f", "2", "-d", ","],
+        "tests/expected/movies2.csv.f2.dcomma.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn csv_quoted_f3() -> Result<()> {
+    run(
+        &[CSV2, "-f", "3", "-d", ","],
+        "tests/expected/movies2.csv.f3.dcomma.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn csv_quoted_f1_3() -> Result<()> {
+    run(
+        &[CSV2, "-f", "1-3", "-d", ","],
+        "tests/expected/movies2.csv.f1-3.dcomma.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn csv_quoted_last_field() -> Result<()> {
+    run(
+        &[BOOKS_CSV, "-f", "3", "-d", ","],
+        "tests/expected/books.csv.f3.dcomma.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn csv_no_csv_splits_quoted_fields() -> Result<()> {
+    run(
+        &[BOOKS_CSV, "-f", "3", "-d", ",", "--no-csv"],
+        "tests/expected/books.csv.f3.dcomma.no-csv.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn csv_escape() -> Result<()> {
+    Command::cargo_bin(PRG)?
+        .args(["-d", ",", "--escape", "\\", "-f", "2,3"])
+        .write_stdin("a,\"say \\\"hi\\\", then go\",d\n")
+        .assert()
+        .success()
+        .stdout("\"say \\\"hi\\\", then go\",d\n");
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn csv_flexible_by_default() -> Result<()> {
+    Command::cargo_bin(PRG)?
+        .args(["--csv", "-f", "2", "tests/inputs/movies2.tsv"])
+        .assert()
+        .success()
+        .stdout("year\n1980\n2019\n1967\n");
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn dies_csv_strict_unequal_lengths() -> Result<()> {
+    dies(
+        &["--csv", "--strict", "-f", "1", "tests/inputs/movies2.tsv"],
+        "found record with 3 fields, but the previous record has 2 fields",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn header_columns_by_name() -> Result<()> {
+    run(
+        &[
+            BOOKS_CSV,
+            "-d",
+            ",",
+            "--header",
+            "--reorder",
+            "--columns",
+            "Title,Author",
+        ],
+        "tests/expected/books.csv.columns.Title,Author.out",
+    )?;
+    run(
+        &[
+            BOOKS_CSV,
+            "-d",
+            ",",
+            "--header",
+            "--reorder",
+            "-f",
+            "3,Author",
+        ],
+        "tests/expected/books.csv.columns.Title,Author.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn header_columns_by_regex() -> Result<()> {
+    run(
+        &[BOOKS, "--header", "--columns", "/^[TY]/"],
+        "tests/expected/books.tsv.columns.regex.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn dies_header_unknown_column() -> Result<()> {
+    dies(
+        &[BOOKS, "--header", "--columns", "Author,Budget"],
+        r#"tests/inputs/books.tsv: unknown column: "Budget""#,
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn dies_columns_without_header() -> Result<()> {
+    dies(
+        &[BOOKS, "--columns", "Author"],
+        r#"illegal list value: "Author""#,
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn open_range_to_end() -> Result<()> {
+    run(
+        &[TSV, "--open-ranges", "-f", "2-"],
+        "tests/expected/movies1.tsv.f2-.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn open_range_from_start() -> Result<()> {
+    run(
+        &[TSV, "--open-ranges", "-c", "-5"],
+        "tests/expected/movies1.tsv.c-5.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn dies_open_range_without_opting_in() -> Result<()> {
+    dies(&[TSV, "-f", "2-"], r#"illegal list value: "2-""#)
+}
+
+// --------------------------------------------------
+#[test]
+fn complement_fields() -> Result<()> {
+    run(
+        &[TSV, "--complement", "-f", "2"],
+        "tests/expected/movies1.tsv.f2.complement.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn complement_chars() -> Result<()> {
+    run(
+        &[TSV, "--complement", "-c", "3-8"],
+        "tests/expected/movies1.tsv.c3-8.complement.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn complement_columns() -> Result<()> {
+    run(
+        &[
+            CSV2,
+            "-d",
+            ",",
+            "--header",
+            "--complement",
+            "--columns",
+            "year",
+        ],
+        "tests/expected/movies2.csv.columns.year.complement.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn multi_byte_delimiter() -> Result<()> {
+    Command::cargo_bin(PRG)?
+        .args(["-d", "::", "-f", "3,1"])
+        .write_stdin("a::b:c::d\nÉmile::x::Zola\n")
+        .assert()
+        .success()
+        .stdout("a::d\nÉmile::Zola\n");
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn utf8_delimiter() -> Result<()> {
+    Command::cargo_bin(PRG)?
+        .args(["-d", "¦", "-f", "2", "--output-delimiter", ","])
+        .write_stdin("Zola¦1865¦Paris\n")
+        .assert()
+        .success()
+        .stdout("1865\n");
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn regex_delimiter() -> Result<()> {
+    Command::cargo_bin(PRG)?
+        .args(["--regex-delimiter", r"\s*[;|]\s*", "-f", "1,3"])
+        .write_stdin("a ; b|c\nd|e  ;  f\n")
+        .assert()
+        .success()
+        .stdout("a\tc\nd\tf\n");
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn output_delimiter() -> Result<()> {
+    run(
+        &[TSV, "-f", "1,3", "--output-delimiter", " | "],
+        "tests/expected/movies1.tsv.f1,3.output-delimiter.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn csv_output_delimiter() -> Result<()> {
+    run(
+        &[
+            BOOKS_CSV,
+            "-d",
+            ",",
+            "-f",
+            "1,3",
+            "--output-delimiter",
+            "\t",
+        ],
+        "tests/expected/books.csv.f1,3.output-delimiter.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn whitespace() -> Result<()> {
+    for flag in ["-w", "--whitespace"] {
+        run(&[PS, flag, "-f", "1,4"], "tests/expected/ps.txt.w.f1,4.out")?;
+    }
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn whitespace_output_delimiter() -> Result<()> {
+    Command::cargo_bin(PRG)?
+        .args(["-w", "-f", "2-3", "--output-delimiter", ","])
+        .write_stdin("  a  b\t\tc d  \n\n e\n")
+        .assert()
+        .success()
+        .stdout("b,c\n\n\n");
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn dies_whitespace_with_delimiter() -> Result<()> {
+    dies(&[PS, "-w", "-d", ",", "-f", "1"], "cannot be used with")
+}
+
+// --------------------------------------------------
+#[test]
+fn fixed_width() -> Result<()> {
+    run(
+        &[BOOKS_TXT, "--fixed-width", "-f", "1,3"],
+        "tests/expected/books.txt.fixed-width.f1,3.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn fixed_width_columns() -> Result<()> {
+    run(
+        &[
+            BOOKS_TXT,
+            "--fixed-width",
+            "--header",
+            "--reorder",
+            "--columns",
+            "Title,Year",
+        ],
+        "tests/expected/books.txt.fixed-width.columns.Title,Year.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn fixed_width_explicit_widths() -> Result<()> {
+    run(
+        &[
+            BOOKS_TXT,
+            "--widths",
+            "6,14",
+            "-f",
+            "1-2",
+            "--output-delimiter",
+            "|",
+        ],
+        "tests/expected/books.txt.widths6,14.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn dies_zero_width() -> Result<()> {
+    dies(
+        &[BOOKS_TXT, "--widths", "20,0", "-f", "1"],
+        "invalid value '0' for '--widths <WIDTHS>'",
+    )
+}
+
+// --------------------------------------------------
+const LOG: &str = "12:00\tINFO\tstarted\nrestarting worker...\n12:01\tWARN\tslow\n\n";
+
+// --------------------------------------------------
+#[test]
+fn undelimited_lines_unchanged() -> Result<()> {
+    Command::cargo_bin(PRG)?
+        .args(["-f", "2"])
+        .write_stdin(LOG)
+        .assert()
+        .success()
+        .stdout("INFO\nrestarting worker...\nWARN\n\n");
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn only_delimited() -> Result<()> {
+    for flag in ["-s", "--only-delimited"] {
+        Command::cargo_bin(PRG)?
+            .args([flag, "-f", "1,3"])
+            .write_stdin(LOG)
+            .assert()
+            .success()
+            .stdout("12:00\tstarted\n12:01\tslow\n");
+    }
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn only_delimited_csv() -> Result<()> {
+    Command::cargo_bin(PRG)?
+        .args(["-s", "-d", ",", "-f", "2"])
+        .write_stdin("a,\"b,c\"\nnoise\n")
+        .assert()
+        .success()
+        .stdout("\"b,c\"\n");
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn dies_only_delimited_with_bytes() -> Result<()> {
+    dies(
+        &[CSV, "-s", "-b", "1"],
+        "'--only-delimited' cannot be used with '--bytes <BYTES>'",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn dies_header_with_chars() -> Result<()> {
+    dies(
+        &[BOOKS, "--header", "-c", "1"],
+        "'--header' cannot be used with '--chars <CHARS>'",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn dies_output_delimiter_with_bytes() -> Result<()> {
+    dies(
+        &[CSV, "--output-delimiter", ":", "-b", "1"],
+        "'--output-delimiter <STRING>' cannot be used with '--bytes <BYTES>'",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn bytes_no_split() -> Result<()> {
+    Command::cargo_bin(PRG)?
+        .args(["-n", "-b", "1,2-4"])
+        .write_stdin("Émile Zola\n日本\n")
+        .assert()
+        .success()
+        .stdout("Émi\n日\n");
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn graphemes() -> Result<()> {
+    Command::cargo_bin(PRG)?
+        .args(["--graphemes", "-c", "1,3-"])
+        .arg("--open-ranges")
+        .write_stdin("e\u{301}te\u{301}\n👩‍👩‍👧 family\n")
+        .assert()
+        .success()
+        .stdout("e\u{301}e\u{301}\n👩‍👩‍👧family\n");
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn chars_split_graphemes() -> Result<()> {
+    Command::cargo_bin(PRG)?
+        .args(["-c", "1"])
+        .write_stdin("e\u{301}te\u{301}\n")
+        .assert()
+        .success()
+        .stdout("e\n");
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn dies_graphemes_with_bytes() -> Result<()> {
+    dies(
+        &[BOOKS, "--graphemes", "-b", "1"],
+        "'--graphemes' cannot be used with '--bytes <BYTES>'",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn csv_keeps_blank_lines() -> Result<()> {
+    run(
+        &[CSV3, "-f", "1,3", "-d", ","],
+        "tests/expected/movies3.csv.f1,3.dcomma.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn csv_only_delimited_drops_blank_lines() -> Result<()> {
+    run(
+        &[CSV3, "-s", "-f", "1,3", "-d", ","],
+        "tests/expected/movies3.csv.f1,3.dcomma.s.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn stops_quietly_when_output_is_closed() -> Result<()> {
+    let mut child = process::Command::new(assert_cmd::cargo::cargo_bin(PRG))
+        .args(["-f", "1"])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()?;
+    let mut stdin = child.stdin.take().unwrap();
+    thread::spawn(move || stdin.write_all("a\tb\n".repeat(100_000).as_bytes()));
+
+    let mut first = String::new();
+    BufReader::new(child.stdout.take().unwrap()).read_line(&mut first)?;
+    let output = child.wait_with_output()?;
+    assert_eq!(first, "a\n");
+    assert!(output.status.success());
+    assert_eq!(String::from_utf8(output.stderr)?, "");
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn bytes_split_characters() -> Result<()> {
+    Command::cargo_bin(PRG)?
+        .args(["-b", "1"])
+        .write_stdin("Émile\n")
+        .assert()
+        .success()
+        .stdout(b"\xc3\n".as_slice());
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn fields_keep_invalid_utf8() -> Result<()> {
+    Command::cargo_bin(PRG)?
+        .args(["-f", "2"])
+        .write_stdin(b"a\t\xe9t\xe9\n".as_slice())
+        .assert()
+        .success()
+        .stdout(b"\xe9t\xe9\n".as_slice());
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn whitespace_single_word() -> Result<()> {
+    Command::cargo_bin(PRG)?
+        .args(["-w", "-f", "1"])
+        .write_stdin("  solo  \n")
+        .assert()
+        .success()
+        .stdout("solo\n");
+    Ok(())
 }